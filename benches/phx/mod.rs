@@ -0,0 +1,45 @@
+//! Phoenix programs measured by the `vm` benchmark.
+//! There are no loops yet, so each program repeats its steps enough times to run for a while.
+
+use std::{fs, path::{Path, PathBuf}};
+
+/// Name and source of every program, each is measured on its own
+pub fn programs() -> [(&'static str, String); 4] {
+    [("fibonacci", fibonacci()), ("interest", interest()), ("strings", strings()), ("sized_ints", sized_ints())]
+}
+
+/// Writes the program as a project named `name` under `dir`, ready to be compiled
+pub fn write_project(dir: &Path, name: &str, src: &str) -> PathBuf {
+    let path = dir.join(name);
+    fs::create_dir_all(&path).unwrap();
+    fs::write(path.join("Feather.toml"), format!("[main]\nproject-id = \"{name}\"\nversion = 1\n")).unwrap();
+    fs::write(path.join("main.phx"), src).unwrap();
+    path
+}
+
+/// Rounds of a program separated by blank lines, after the declarations of its variables
+fn program(decls: &str, rounds: impl Iterator<Item = String>) -> String { format!("{decls}\n{}", rounds.collect::<Vec<_>>().join("\n")) }
+
+/// Sums the 80th Fibonacci number over and over
+fn fibonacci() -> String {
+    let round = format!("a = 0\nb = 1\n{}sum = sum + b / 1000 * 3 - a / 7\n", "b = a + b\na = b - a\n".repeat(80));
+    program("let a = 0\nlet b = 1\nlet sum = 0\n", std::iter::repeat_n(round, 10))
+}
+
+/// Compounds a monthly interest rate for 25 years with exact Dec arithmetic
+fn interest() -> String {
+    let year = format!("{}paid = paid + principal / 100\n", "principal = principal + principal * rate / 12\n".repeat(12));
+    program("let principal = 1000.00\nlet rate = 0.05\nlet paid = 0.0\n", std::iter::repeat_n(year, 25))
+}
+
+/// Builds strings by concatenation and interpolation
+fn strings() -> String {
+    let line = format!("line = \"\"\n{}line = \"{{line}}: {{count}}\"\n", "line = line + \"ab\"\nline = line + 'c'\ncount = count + 1\n".repeat(10));
+    program("let line = \"\"\nlet count = 0\n", std::iter::repeat_n(line, 20))
+}
+
+/// Mixes sized integers, which go through the generic opcodes, with wrapping and saturating operators
+fn sized_ints() -> String {
+    let step = |i: u32| format!("hash = hash *% 16777619u32 +% {}u32\nsmall = small +| 1000i16\nbyte = byte +% 37u8\nhash = hash -% byte as U32\n", 1 + 7 * i);
+    program("let hash = 2166136261u32\nlet small = 0i16\nlet byte = 0u8\n", (0..40).map(step))
+}
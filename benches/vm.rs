@@ -0,0 +1,20 @@
+use std::path::PathBuf;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use phoenixlang::{Compiler, Profile, StackConfig, source::SourceMap};
+
+mod phx;
+
+/// Programs are compiled once as release builds, only running them is measured
+pub fn programs(c: &mut Criterion) {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("phx");
+    for (name, src) in phx::programs() {
+        let chunk = Compiler::compile(phx::write_project(&dir, name, &src), Profile::Release, &mut SourceMap::default())
+            .unwrap_or_else(|errors| panic!("{name} does not compile: {errors:?}"));
+
+        c.bench_function(name, |b| b.iter_batched(|| chunk.clone(), |chunk| phoenixlang::run(chunk, StackConfig::default(), false).unwrap(), BatchSize::SmallInput));
+    }
+}
+
+criterion_group!(benches, programs);
+criterion_main!(benches);
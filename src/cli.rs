@@ -1,5 +1,5 @@
 use std::path::PathBuf;
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Debug)]
 pub struct PhoenixCli {
@@ -15,6 +15,10 @@ pub enum Commands {
         /// Whether to print scanned input or not
         #[arg(short, long)]
         scan: bool,
+
+        /// Prints an intermediate representation of the project instead of compiling it
+        #[arg(long, value_enum)]
+        emit: Option<Emit>,
         
         /// Must be a directory with a Feather.toml
         project: PathBuf,
@@ -34,7 +38,21 @@ pub enum Commands {
         #[arg(short, long)]
         debug: bool,
 
+        /// Values the stack starts with, overrides the stack-size of the Feather.toml
+        #[arg(long)]
+        stack_size: Option<usize>,
+
+        /// Values the stack can grow to, overrides the max-stack-size of the Feather.toml
+        #[arg(long)]
+        max_stack_size: Option<usize>,
+
         /// Can be a .flms or a directory with a Feather.toml
         file: PathBuf,
     }
 }
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Emit {
+    /// Syntax tree of the main file with the type of every expression
+    Ast,
+}
@@ -1,33 +1,43 @@
 use core::panic;
 use std::{collections::{HashSet, HashMap}, io::Read, fmt::Debug};
 
-use crate::{flamebytecode::FBOpCode, vm::value::Value};
+use crate::{flamebytecode::FBOpCode, vm::value::Value, decimal::Decimal};
 
+#[derive(Clone)]
 pub struct Chunk {
     pub consts: ConstPool,
     pub code: Vec<u8>,
 }
 
+#[derive(Clone)]
 pub enum ConstPool {
     Compiler { hash: HashMap<Const, u32>, len: u32, },
     Vm(Vec<Const>),
 }
 
 
-#[derive(Eq, Hash, PartialEq)]
+#[derive(Clone, Eq, Hash, PartialEq)]
 pub enum Const {
     Int(i64),
-    Dec(u64),
+    Dec(Decimal),
     String(Box<str>),
-    Char(char)
+    Char(char),
+    I8(i8), I16(i16), I32(i32), I64(i64),
+    U8(u8), U16(u16), U32(u32), U64(u64),
+    F32(u32), F64(u64),
 }
 
 impl Debug for Const { fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     match self {
         Const::String(v) => write!(f, "str: '{v}'"),
         Const::Int(v) => write!(f, "int: {v}"),
-        Const::Dec(v) => write!(f, "dec: {}", f64::from_bits(*v)),
+        Const::Dec(v) => write!(f, "dec: {v}"),
         Const::Char(v) => write!(f, "char: '{v}'"),
+        Const::I8(v) => write!(f, "i8: {v}"), Const::I16(v) => write!(f, "i16: {v}"),
+        Const::I32(v) => write!(f, "i32: {v}"), Const::I64(v) => write!(f, "i64: {v}"),
+        Const::U8(v) => write!(f, "u8: {v}"), Const::U16(v) => write!(f, "u16: {v}"),
+        Const::U32(v) => write!(f, "u32: {v}"), Const::U64(v) => write!(f, "u64: {v}"),
+        Const::F32(v) => write!(f, "f32: {:?}", f32::from_bits(*v)), Const::F64(v) => write!(f, "f64: {:?}", f64::from_bits(*v)),
     }}}
 
 impl ConstPool {
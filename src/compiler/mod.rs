@@ -3,14 +3,15 @@ use ahash::AHasher;
 use clap::error::ErrorKind;
 use toml::Table;
 
-use crate::{error::{PhoenixError, CompErrID}, compiler, strings::InternStrSync};
+use crate::{error::{PhoenixError, CompErrID}, compiler, strings::InternStrSync, source::{SourceMap, FileId}, vm::StackConfig};
 
-use self::{module::Module, scanner::Scanner, chunk::Chunk};
+use self::{module::Module, scanner::{Scanner, Indent}, chunk::Chunk};
 
 pub mod chunk;
 pub mod scanner;
-mod token;
+pub mod token;
 pub mod module;
+pub mod peephole;
 
 type AHashMap<K, V> = HashMap<K, V, BuildHasherDefault<AHasher>>;
 
@@ -20,26 +21,17 @@ pub struct Compiler {
     transmitter: Option<Sender<JoinHandle<Result<(), Vec<PhoenixError>>>>>
 }
 
+/// How the code is built, only release builds are optimized
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile { Debug, Release }
+
 impl Compiler {
     pub fn new(intern_str: InternStrSync) -> Self { Self { modules: AHashMap::default(), strings: intern_str, transmitter: None }}
 
-    pub fn compile(path: PathBuf) -> Result<Chunk, Vec<PhoenixError>> {// Todo Temp chunk    
-        macro_rules! config_err { ($($arg:tt)*) => { vec![PhoenixError::Config(format!($($arg)*))] }; }
-
-        let feather_toml = path.join("Feather.toml");
-        if !path.is_dir() || !feather_toml.is_file() { 
-            return Err(vec![PhoenixError::Cli(ErrorKind::InvalidValue, format!("Given project must be a directory containing a Feather.toml"))]) }
-
-        let confs = fs::read_to_string(feather_toml).unwrap();
-        let confs = Table::from_str(&confs).map_err(|err| vec![PhoenixError::Cli(ErrorKind::Io, format!("{err}"))])?;
-        if let Some(app_map) = confs.get("main") {
-            if !app_map.is_table() { return Err(config_err!("'main' field in 'Feather.toml' isn't a table")) }
-
-            let name = app_map.get("project-id").ok_or_else(|| config_err!("The 'main' table must specify a project-id"))?
-                .as_str().ok_or_else(|| config_err!("The project-id in 'main' must be a string"))?.to_owned();
-            let version = app_map.get("version").ok_or_else(|| config_err!("The 'main' table must specify a version"))?;
-
-            let main = path.join("main.phx"); if !main.is_file() { return Err(config_err!("Missing main.phx in project directory")); }
+    /// Files read while compiling are registered in `sources`, which resolves the spans of returned errors
+    pub fn compile(path: PathBuf, profile: Profile, sources: &mut SourceMap) -> Result<Chunk, Vec<PhoenixError>> {// Todo Temp chunk    
+        if let Some(Project { name, file, src, max_identifier_length, indent }) = Project::load(&path, sources)? {
+            let source = sources.get(file).clone();
 
             let mut intern_str = InternStrSync::new();
 
@@ -52,11 +44,15 @@ impl Compiler {
             let compiler_two = compiler.clone();
             let idd = id.clone();
             let main_thread = thread::spawn(move || {
+                let (tokens, symbols, mut errors) = Scanner::new(src, file).with_max_identifier_length(max_identifier_length).with_indent(indent).scan();
                 let mut module = Module::new(
-                    Scanner::new(fs::read_to_string(main).map_err(|err| config_err!("{err}"))?).scan().map_err(|err| vec![err])?,
+                    tokens, symbols,
                     idd.clone(),
+                    source,
                     compiler_two.clone());
-                module.compile(txx)?;
+                // Lexing errors are reported along with the parsing errors of the rest of the file
+                if let Err(parse_errors) = module.compile(txx) { errors.extend(parse_errors) }
+                if !errors.is_empty() { return Err(errors) }
 
                 let mut compiler_two = compiler_two.lock().unwrap();
                 compiler_two.modules.insert(idd, module);
@@ -71,11 +67,100 @@ impl Compiler {
             }
             
             let mut compiler = Arc::into_inner(compiler).unwrap().into_inner().unwrap();
-            let chunk = compiler.modules.get_mut(&id).unwrap().chunk.take().unwrap();
+            let mut chunk = compiler.modules.get_mut(&id).unwrap().chunk.take().unwrap();
+            if profile == Profile::Release { peephole::optimize(&mut chunk) }
             return Ok(chunk.build());
         }
 
         Ok(Chunk::new())
     }
+
+    /// Typed syntax tree of the project's main file, one node per line
+    pub fn emit_ast(path: PathBuf, sources: &mut SourceMap) -> Result<String, Vec<PhoenixError>> {
+        let Some(Project { name, file, src, max_identifier_length, indent }) = Project::load(&path, sources)? else { return Ok(String::new()) };
+        let source = sources.get(file).clone();
+
+        let mut intern_str = InternStrSync::new();
+        let id = intern_str.intern_str(&name);
+        let compiler = Arc::new(Mutex::new(Compiler::new(intern_str)));
+
+        let (tokens, symbols, mut errors) = Scanner::new(src, file).with_max_identifier_length(max_identifier_length).with_indent(indent).scan();
+        let mut module = Module::new(tokens, symbols, id, source, compiler);
+        match module.typed_ast() {
+            Ok(stmts) if errors.is_empty() => Ok(module::ast::dump(&stmts)),
+            Ok(_) => Err(errors),
+            Err(check_errors) => { errors.extend(check_errors); Err(errors) }
+        }
+    }
+}
+
+macro_rules! config_err { ($($arg:tt)*) => { vec![PhoenixError::Config(format!($($arg)*))] }; }
+
+impl Compiler {
+    /// Stack the project runs with, set by the optional 'vm' table of its Feather.toml
+    pub fn stack_config(path: &PathBuf) -> Result<StackConfig, Vec<PhoenixError>> {
+        let confs = feather_toml(path)?;
+        let vm = match confs.get("vm") {
+            Some(vm) => vm.as_table().ok_or_else(|| config_err!("'vm' field in 'Feather.toml' isn't a table"))?.clone(),
+            None => Table::new(),
+        };
+        let slots = |key: &str| match vm.get(key) {
+            Some(slots) => slots.as_integer().filter(|slots| *slots > 0).map(|slots| Some(slots as usize))
+                .ok_or_else(|| config_err!("The {key} in 'vm' must be a positive integer")),
+            None => Ok(None),
+        };
+        StackConfig::default().with_sizes(slots("stack-size")?, slots("max-stack-size")?).map_err(|msg| config_err!("{msg}"))
+    }
+}
+
+/// Parsed Feather.toml of the project directory
+fn feather_toml(path: &PathBuf) -> Result<Table, Vec<PhoenixError>> {
+    let feather_toml = path.join("Feather.toml");
+    if !path.is_dir() || !feather_toml.is_file() { 
+        return Err(vec![PhoenixError::Cli(ErrorKind::InvalidValue, format!("Given project must be a directory containing a Feather.toml"))]) }
+
+    let confs = fs::read_to_string(feather_toml).unwrap();
+    Table::from_str(&confs).map_err(|err| vec![PhoenixError::Cli(ErrorKind::Io, format!("{err}"))])
+}
+
+/// Project described by a Feather.toml, with its main file already registered in the source map
+struct Project { name: String, file: FileId, src: String, max_identifier_length: Option<usize>, indent: Indent }
+
+impl Project {
+    /// `None` if the Feather.toml has no 'main' table
+    fn load(path: &PathBuf, sources: &mut SourceMap) -> Result<Option<Self>, Vec<PhoenixError>> {
+        let confs = feather_toml(path)?;
+        let Some(app_map) = confs.get("main") else { return Ok(None) };
+        if !app_map.is_table() { return Err(config_err!("'main' field in 'Feather.toml' isn't a table")) }
+
+        let name = app_map.get("project-id").ok_or_else(|| config_err!("The 'main' table must specify a project-id"))?
+            .as_str().ok_or_else(|| config_err!("The project-id in 'main' must be a string"))?.to_owned();
+        let version = app_map.get("version").ok_or_else(|| config_err!("The 'main' table must specify a version"))?;
+
+        let syntax = match confs.get("syntax") {
+            Some(syntax) => syntax.as_table().ok_or_else(|| config_err!("'syntax' field in 'Feather.toml' isn't a table"))?.clone(),
+            None => Table::new(),
+        };
+        let max_identifier_length = match syntax.get("identifier-max-length") {
+            Some(max) => Some(max.as_integer().filter(|max| *max > 0)
+                .ok_or_else(|| config_err!("The identifier-max-length in 'syntax' must be a positive integer"))? as usize),
+            None => None,
+        };
+        let indent_width = match syntax.get("indent-width") {
+            Some(width) => width.as_integer().filter(|width| (1..=u8::MAX as i64).contains(width))
+                .ok_or_else(|| config_err!("The indent-width in 'syntax' must be an integer between 1 and 255"))? as u8,
+            None => 4,
+        };
+        let indent = match syntax.get("indent-style").map(|style| style.as_str()) {
+            None | Some(Some("spaces")) => Indent::Spaces(indent_width),
+            Some(Some("tabs")) => Indent::Tabs,
+            Some(_) => return Err(config_err!("The indent-style in 'syntax' must be either \"spaces\" or \"tabs\"")),
+        };
+
+        let main = path.join("main.phx"); if !main.is_file() { return Err(config_err!("Missing main.phx in project directory")); }
+        let src = fs::read_to_string(&main).map_err(|err| config_err!("{err}"))?;
+        let file = sources.add(&main.to_string_lossy(), &src);
+        Ok(Some(Self { name, file, src, max_identifier_length, indent }))
+    }
 }
 
@@ -4,39 +4,59 @@ use std::hash::BuildHasherDefault;
 use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
-use std::{string::String, any::TypeId};
+use std::{string::String, any::TypeId, borrow::Cow};
 use ahash::AHasher;
 use clap::builder::Str;
 use crate::error::CompErrID;
+use crate::decimal::{Decimal, MAX_SCALE};
 use crate::flamebytecode::FBOpCode;
-use crate::{error::PhoenixError, debug::debug_chunk};
+use crate::{error::PhoenixError, debug::debug_chunk, source::{SourceFile, Span}};
 
-use self::logic::{plus, minus, star, slash, negate};
-use self::types::Type;
+use self::types::{Type, Alias};
+use self::infer::Infer;
+use self::ast::Stmt;
+use self::fold::ConstValue;
 
 use crate::FBOpCode::*;
 use super::Compiler;
 use super::token::TokenType;
-use super::{token::{Token, self, TokenType::*}, chunk::{Chunk, Const}};
+use super::{token::{Token, self, TokenType::*, Symbols}, chunk::{Chunk, Const}};
 
+pub mod ast;
+mod parser;
+mod check;
+mod codegen;
+mod fold;
 mod types;
 mod logic;
+mod infer;
 
 type AHashMap<K, V> = HashMap<K, V, BuildHasherDefault<AHasher>>;
 
+/// Deepest nesting of expressions and blocks a module may use
+const MAX_NESTING: u8 = 128;
+
 pub struct Module {
     id: Arc<str>,
     tokens: Vec<Token>, i: usize,
+    symbols: Symbols,
     // TODO Temp, will have a locals for each func, not for curr chunk
     locals: Vec<Local>, scope_depth: u8,
+    /// Expressions and blocks being parsed, bounded so no source can overflow the stack
+    nesting: u8,
+    infer: Infer,
 
     imports: AHashMap<Arc<str>, Arc<String>>,
     funcs: AHashMap<Arc<str>, Funcs>,
     globals: AHashMap<Arc<str>, Type>,
+    aliases: AHashMap<Arc<str>, Alias>,
+    /// Value and type of every `const`, inlined where they are used
+    consts: AHashMap<Arc<str>, (Type, ConstValue)>,
     // TODO chunk is temporary, will return module result table
     pub chunk: Option<Chunk>,
 
     compiler: Option<Arc<Mutex<Compiler>>>,
+    source: Arc<SourceFile>,
 }
 
 struct Funcs {
@@ -48,242 +68,45 @@ struct Funcs {
 struct Local { name: Arc<str>, depth: u8, ty: Type }
 
 impl Module {
-    pub fn new(tokens: Vec<Token>, id: Arc<str>, compiler: Arc<Mutex<Compiler>>) -> Self { 
+    pub fn new(tokens: Vec<Token>, symbols: Symbols, id: Arc<str>, source: Arc<SourceFile>, compiler: Arc<Mutex<Compiler>>) -> Self { 
         Self { 
-            tokens, id, i: 0,
+            tokens, symbols, id, i: 0, source,
             compiler: Some(compiler),
-            imports: AHashMap::default(), funcs: Default::default(), globals: Default::default(),
+            imports: AHashMap::default(), funcs: Default::default(), globals: Default::default(), aliases: Default::default(), consts: Default::default(),
             chunk: Some(Chunk::new()),
-            locals: Vec::with_capacity(0xFF), scope_depth: 0, 
+            locals: Vec::with_capacity(0xFF), scope_depth: 0, nesting: 0, infer: Infer::new(),
         }
     }
 
+    /// Zero based line the span starts on
     #[inline(always)]
-    pub fn curr_tok(&mut self) -> &mut Token { &mut self.tokens[self.i] }
-    
-    pub fn compile(&mut self, tx: Sender<JoinHandle<Result<(), Vec<PhoenixError>>>>) -> Result<(), Vec<PhoenixError>> {
-        let mut errors = vec![];
+    fn line(&self, span: Span) -> usize { self.source.line(span.start) }
 
-        while self.curr_tok().ty != Eof {
-            let err = self.loose_statement();
-            if err.is_err() { 
-                errors.push(err.unwrap_err());
-                loop {
-                    let end_statement = self.curr_tok().pos.0 != self.tokens[self.i + 1].pos.0 || [Eof, SemiColon].contains(&self.curr_tok().ty);
-                    self.i += 1;
-                    if end_statement { break; }
-                }
-            }
-        }
-
-        self.compiler = None;
-        if errors.is_empty() { Ok(()) } else { Err(errors) }
-    }
-
-    pub fn loose_statement(&mut self) -> Result<(), PhoenixError> {
-        if self.tokens[self.i].lexeme.as_ref().is_some_and(|str| &str[1..] == "print") { //TODO temporary print
-            self.i += 1;
-            let pos = self.tokens[self.i].pos;
-            let ty = self.expression_parsing(0)?;
-            if self.curr_tok().ty == SemiColon { self.i += 1 }
-            match ty { Type::Void => return Err(PhoenixError::Compile { id: CompErrID::TypeError, row: pos.0, col: pos.1, msg: String::from("print statement requires a non-void expression") }), _ => {} }
-            self.chunk.as_mut().unwrap().write_op(FBOpCode::OpPrint);
-            return Ok(());
-        }
-        let ty = self.expression_parsing(0)?;
-        if self.curr_tok().ty == SemiColon { self.i += 1 }
-        match ty { Type::Void => {} _ => self.chunk.as_mut().unwrap().write_op(FBOpCode::OpPop), }
-        Ok(())
-    }
-
-    pub fn expression_parsing(&mut self, min_bp: u8) -> Result<Type, PhoenixError> {
-        let lht_pos = self.curr_tok().pos;
-        let mut lht = match self.curr_tok().ty {
-            Let => return self._let(),
-            True | False => self.bool(),
-            Int => self.int(),
-            Dec => self.dec(), 
-            String => self.string(),
-            Identifier => { let ty = self.variable()?; if Type::Void == ty { return Ok(ty); } else { ty } }
-            Plus => {
-                self.i += 1;
-                let ret_ty = self.expression_parsing(9)?;
-                self.i -= 1; 
-                ret_ty
-            }
-            Char => self.char(),
-            LParen => {
-                self.i += 1;
-                let value = self.expression_parsing(0)?;
-                assert_eq!(self.curr_tok().ty, RParen);
-                value
-            }
-            op @ Minus => {
-                let ((), r_bp) = prefix_bp(op);
-                let tok_i = self.i; self.i += 1;
-                let rht_pos = self.curr_tok().pos;
-                let rhs = self.expression_parsing(r_bp)?;
-                self.i -= 1;
-                Self::operation(self.chunk.as_mut().unwrap(), None, (rhs, rht_pos), &self.tokens[tok_i])?
-            }
-            op @ (LBrace | IndentUp) => return self.block(if op == LBrace { Some(self.tokens[self.i].pos.1) } else { None }),
-            ty => return Err(PhoenixError::Compile { id: CompErrID::InvalidCharacter, row: self.curr_tok().pos.0, col: self.curr_tok().pos.1, 
-                msg: format!("Invalid character '{ty:?}' at the start of an expression") })
-        };
-        self.i += 1;
-
-        loop {
-            let op_i = self.i;
-            if self.tokens[self.i - 1].pos.0 != self.curr_tok().pos.0 { break; }
-            let op = match self.curr_tok().ty {
-                SemiColon | RParen | Eof  => break,
-                op @ (Plus | Minus | Star | Slash) => &self.tokens[op_i], 
-                op => unreachable!("{op:?}"),
-            };
-            
-            if let Some((l_bp, ())) = postfix_bp(op.ty) { // Postfix
-                if l_bp < min_bp { break; }
-                self.i += 1;
-
-                lht = if op.ty == LSquare {
-                    let rht_pos = self.curr_tok().pos;
-                    let rhs = self.expression_parsing(0)?;
-                    assert_eq!(self.curr_tok().ty, RSquare);
-                    // TODO Lquare get func
-                    Type::Void // TODO type calculator
-                } else { lht };
-                continue;
-            }
-
-            if let Some((l_bp, r_bp)) = infix_bp(op.ty) { // Infix
-                if l_bp < min_bp { break; }
-                self.i += 1;
-                
-                while self.curr_tok().ty == IndentUp || self.curr_tok().ty == IndentDown { self.i += 1; }
+    /// Name of an identifier or content of a string or char literal
+    #[inline(always)]
+    fn sym(&self, i: usize) -> &Arc<str> { self.symbols.get(self.tok(i).sym.unwrap()) }
 
-                lht = {
-                    let rht_pos = self.curr_tok().pos;
-                    let rht = self.expression_parsing(r_bp)?;
-                    let op = &self.tokens[op_i];
-                    Self::operation(self.chunk.as_mut().unwrap(), Some((lht, lht_pos)), (rht, rht_pos), op)?
-                };
-                continue;
-            }
+    #[inline(always)]
+    fn is_print(&self) -> bool { self.tok(self.i).ty == Identifier && &**self.sym(self.i) == "print" }
 
-            break
-        }
+    /// Token at `i`, reading past the end gives the final `Eof`
+    #[inline(always)]
+    fn tok(&self, i: usize) -> &Token { &self.tokens[i.min(self.tokens.len() - 1)] }
 
-        Ok(lht)
-    }
+    #[inline(always)]
+    pub fn curr_tok(&mut self) -> &mut Token { let i = self.i.min(self.tokens.len() - 1); &mut self.tokens[i] }
     
-   #[inline(always)]
-   fn bool(&mut self) -> Type { let op = if self.curr_tok().ty == True { OpTrue } else { OpFalse }; self.chunk.as_mut().unwrap().write_op(op); Type::Bool }
-   fn int(&mut self) -> Type {
-       let num = self.curr_tok().lexeme.take().unwrap().parse::<i64>().unwrap();
-       self.chunk.as_mut().unwrap().write_const(Const::Int(num));
-       Type::Int
-   }
-   fn dec(&mut self) -> Type {
-       let num = self.curr_tok().lexeme.take().unwrap().parse::<f64>().unwrap();
-       self.chunk.as_mut().unwrap().write_const(Const::Dec(num.to_bits()));
-       Type::Dec
-   }
-   fn string(&mut self) -> Type {
-       let str = self.curr_tok().lexeme.take().unwrap();
-       self.chunk.as_mut().unwrap().write_const(Const::String(str));
-       Type::Str
-   }
-   fn char(&mut self) -> Type {
-       let char = self.curr_tok().lexeme.take().unwrap().chars().next().unwrap();
-       self.chunk.as_mut().unwrap().write_const(Const::Char(char));
-       Type::Char
-   }
-   fn block(&mut self, brace_row: Option<u16>) -> Result<Type, PhoenixError> {
-       // begin scope
-       self.i += 1;
-       self.scope_depth += 1;
-       let mut block_ty = Type::Void;
-
-       while ![Eof, RBrace, IndentDown].contains(&self.curr_tok().ty) {
-           if block_ty != Type::Void { self.chunk.as_mut().unwrap().write_op(FBOpCode::OpPop) }
-
-           if self.tokens[self.i].lexeme.as_ref().is_some_and(|str| &str[1..] == "print") { //TODO temporary print
-               self.i += 1;
-               let pos = self.tokens[self.i].pos;
-               let ty = self.expression_parsing(0)?;
-               if self.curr_tok().ty == SemiColon { self.i += 1 }
-               match ty { Type::Void => return Err(PhoenixError::Compile { id: CompErrID::TypeError, row: pos.0, col: pos.1, 
-                   msg: String::from("print statement requires a non-void expression") }), _ => {} }
-               self.chunk.as_mut().unwrap().write_op(FBOpCode::OpPrint);
-               block_ty = Type::Void; continue;
-           }
-           block_ty = self.expression_parsing(0)?;
-           if self.curr_tok().ty == SemiColon { self.i += 1 }
-       }
-
-       match brace_row { // Checks for different types of blocks
-           Some(start) if self.curr_tok().pos.0 != start => { // If opener is '{'
-               let end = self.curr_tok().pos;
-               return Err(PhoenixError::Compile { id: CompErrID::InvalidCharacter, row: end.0, col: end.1, 
-                   msg: format!("Braces can be used only for single-line blocks") })
-           }
-           None if self.curr_tok().ty == RBrace => { // If opener is a tab
-               let end = self.curr_tok().pos;
-               return Err(PhoenixError::Compile { id: CompErrID::InvalidCharacter, row: end.0, col: end.1, 
-                   msg: format!("Cannot terminate multiline block with '}}'") })
-           } _ => {}
-       }
-       self.i += 1;
-
-       if self.tokens[self.i - 1].ty == SemiColon && block_ty != Type::Void { self.chunk.as_mut().unwrap().write_op(FBOpCode::OpPop); block_ty = Type::Void; }
-
-       // end scope
-       self.scope_depth -= 1;
-       while self.locals.len() > 0 && self.locals[self.locals.len() - 1].depth > self.scope_depth { self.locals.pop(); self.chunk.as_mut().unwrap().write_op(FBOpCode::OpPop); }
-       Ok(block_ty)
-   }
-
-
-   fn operation(chunk: &mut Chunk, lht: Option<(Type, (u16, u16))>, rht: (Type, (u16, u16)), op: &Token) -> Result<Type, PhoenixError> {
-       match op.ty {
-           Plus | PlusEq => plus(chunk, lht.unwrap(), rht, op),
-           Minus | MinusEq => if lht.is_some() { minus(chunk, lht.unwrap(), rht, op) } else { negate(chunk, rht, op) }
-           Star | StarEq => star(chunk, lht.unwrap(), rht, op),
-           Slash | SlashEq => slash(chunk, lht.unwrap(), rht, op),
-           _ => todo!()
-       }
-   }
-}
-
-// bp stands for binding power
+    pub fn compile(&mut self, tx: Sender<JoinHandle<Result<(), Vec<PhoenixError>>>>) -> Result<(), Vec<PhoenixError>> {
+        let stmts = self.typed_ast();
+        self.compiler = None;
+        self.codegen(&stmts?);
+        Ok(())
+    }
 
-fn prefix_bp(op: TokenType) -> ((), u8) {
-    match op {
-        Plus | Minus => ((), 9),
-        _ => panic!("bad op: {:?}", op),
+    /// Parses and checks the whole module, every error found by either pass is returned
+    pub fn typed_ast(&mut self) -> Result<Vec<Stmt>, Vec<PhoenixError>> {
+        let (mut stmts, mut errors) = self.parse();
+        errors.extend(self.check(&mut stmts));
+        if errors.is_empty() { Ok(stmts) } else { Err(errors) }
     }
 }
-fn postfix_bp(op: TokenType) -> Option<(u8, ())> {
-    let res = match op {
-//        '[' => (11, ()),
-        _ => return None,
-    };
-    Some(res)
-}
-fn infix_bp(op: TokenType) -> Option<(u8, u8)> {
-    let res = match op {
-        Eq | PlusEq | MinusEq | StarEq | SlashEq => (2, 1),
-        Identifier => (4, 3),
-        Plus | Minus => (5, 6),
-        Star | Slash => (7, 8),
-        Dot => (14, 13),
-        _ => return None,
-    };
-    Some(res)
-}
-
-
-
-
-
-
@@ -0,0 +1,125 @@
+use std::{fmt::{Display, Write}, sync::Arc};
+
+use crate::source::Span;
+
+use super::{types::Type, infer::Lit};
+
+/// Statement of a module or of a block, as produced by the parser
+#[derive(Debug)]
+pub enum Stmt {
+    Expr(Expr),
+    Print(Expr),
+    /// `alias Name = Type`, only allowed at module level
+    Alias { name: Arc<str>, span: Span, ty: TypeExpr },
+    /// `const NAME: Type = value`, only allowed at module level, its uses are replaced by the value
+    Const { name: Arc<str>, span: Span, ty: TypeExpr, value: Expr },
+}
+
+/// Expression with the type the checker gave it, `Type::Void` until the module is checked
+#[derive(Debug)]
+pub struct Expr { pub kind: ExprKind, pub span: Span, pub ty: Type }
+
+#[derive(Debug)]
+pub enum ExprKind {
+    Bool(bool),
+    /// Numeric literal, `suffix` is the type it was written with, like the `u8` of `255u8`
+    Number { value: Lit, suffix: Option<Type>, lexeme: Arc<str> },
+    Str(Arc<str>),
+    Char(char),
+    /// Parts of an interpolated string in order, the literal parts are `Str` expressions
+    Interp(Vec<Expr>),
+    Var { name: Arc<str>, binding: Binding },
+    Let { name: Arc<str>, name_span: Span, ty: Option<TypeExpr>, value: Box<Expr>, binding: Binding },
+    /// `name = value`, or `name += value` and the other compound assignments when `op` is set
+    Assign { name: Arc<str>, name_span: Span, op: Option<BinOp>, op_span: Span, value: Box<Expr>, binding: Binding },
+    Neg { op_span: Span, expr: Box<Expr> },
+    Binary { op: BinOp, op_span: Span, lhs: Box<Expr>, rhs: Box<Expr> },
+    Cast { op_span: Span, expr: Box<Expr>, to: TypeExpr },
+    /// `locals` is the number of variables declared directly in the block, set by the checker.
+    /// A `;` after the last statement drops its value and makes the block `Void`, which `void` records
+    Block { stmts: Vec<Stmt>, locals: u32, void: bool },
+}
+
+/// Where the checker found a variable
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Binding { Unresolved, Local(u32), Global }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinOp { Add, Sub, Mul, Div, WrapAdd, WrapSub, WrapMul, SatAdd, SatSub, SatMul }
+
+/// A type as written in the source, `ty` is set once the checker resolved its name
+#[derive(Debug)]
+pub struct TypeExpr { pub name: Arc<str>, pub span: Span, pub ty: Type }
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self { Self { kind, span, ty: Type::Void } }
+}
+
+impl BinOp {
+    /// Name of the operation in type errors
+    pub fn name(self) -> &'static str {
+        match self {
+            BinOp::Add => "plus", BinOp::Sub => "minus", BinOp::Mul => "mul", BinOp::Div => "div",
+            BinOp::WrapAdd => "wrapping_plus", BinOp::WrapSub => "wrapping_minus", BinOp::WrapMul => "wrapping_mul",
+            BinOp::SatAdd => "saturating_plus", BinOp::SatSub => "saturating_minus", BinOp::SatMul => "saturating_mul",
+        }
+    }
+}
+
+impl Display for BinOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BinOp::Add => "+", BinOp::Sub => "-", BinOp::Mul => "*", BinOp::Div => "/",
+            BinOp::WrapAdd => "+%", BinOp::WrapSub => "-%", BinOp::WrapMul => "*%",
+            BinOp::SatAdd => "+|", BinOp::SatSub => "-|", BinOp::SatMul => "*|",
+        })
+    }
+}
+
+/// Indented tree of the statements with the type of every expression, one node per line
+pub fn dump(stmts: &[Stmt]) -> String {
+    let mut out = String::new();
+    stmts.iter().for_each(|stmt| stmt.dump(&mut out, 0));
+    out
+}
+
+impl Stmt {
+    fn dump(&self, out: &mut String, depth: usize) {
+        match self {
+            Stmt::Expr(expr) => expr.dump(out, depth),
+            Stmt::Print(expr) => { let _ = writeln!(out, "{:indent$}Print", "", indent = depth * 2); expr.dump(out, depth + 1) }
+            Stmt::Alias { name, ty, .. } => { let _ = writeln!(out, "{:indent$}Alias {name} = {}", "", ty.ty, indent = depth * 2); }
+            Stmt::Const { name, ty, value, .. } => { let _ = writeln!(out, "{:indent$}Const {name} : {}", "", ty.ty, indent = depth * 2); value.dump(out, depth + 1) }
+        }
+    }
+}
+
+impl Expr {
+    fn dump(&self, out: &mut String, depth: usize) {
+        let indent = depth * 2;
+        let _ = match &self.kind {
+            ExprKind::Bool(v) => write!(out, "{:indent$}Bool {v}", ""),
+            ExprKind::Number { lexeme, .. } => write!(out, "{:indent$}Number {lexeme}", ""),
+            ExprKind::Str(v) => write!(out, "{:indent$}Str {v:?}", ""),
+            ExprKind::Char(v) => write!(out, "{:indent$}Char {v:?}", ""),
+            ExprKind::Interp(_) => write!(out, "{:indent$}Interp", ""),
+            ExprKind::Var { name, binding } => write!(out, "{:indent$}Var {name} {binding:?}", ""),
+            ExprKind::Let { name, binding, .. } => write!(out, "{:indent$}Let {name} {binding:?}", ""),
+            ExprKind::Assign { name, op, binding, .. } => write!(out, "{:indent$}Assign {name} {}= {binding:?}", "", op.map_or(String::new(), |op| op.to_string())),
+            ExprKind::Neg { .. } => write!(out, "{:indent$}Neg", ""),
+            ExprKind::Binary { op, .. } => write!(out, "{:indent$}Binary {op}", ""),
+            ExprKind::Cast { to, .. } => write!(out, "{:indent$}Cast {}", "", to.ty),
+            ExprKind::Block { locals, void, .. } => write!(out, "{:indent$}Block locals={locals}{}", "", if *void { " ;" } else { "" }),
+        };
+        let _ = writeln!(out, " : {}", self.ty);
+
+        match &self.kind {
+            ExprKind::Interp(parts) => parts.iter().for_each(|part| part.dump(out, depth + 1)),
+            ExprKind::Let { value, .. } | ExprKind::Assign { value, .. } => value.dump(out, depth + 1),
+            ExprKind::Neg { expr, .. } | ExprKind::Cast { expr, .. } => expr.dump(out, depth + 1),
+            ExprKind::Binary { lhs, rhs, .. } => { lhs.dump(out, depth + 1); rhs.dump(out, depth + 1) }
+            ExprKind::Block { stmts, .. } => stmts.iter().for_each(|stmt| stmt.dump(out, depth + 1)),
+            _ => {}
+        }
+    }
+}
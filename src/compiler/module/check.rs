@@ -0,0 +1,163 @@
+use std::str::FromStr;
+
+use crate::error::{PhoenixError, CompErrID};
+
+use super::{Module, ast::{Stmt, Expr, ExprKind}, types::{Type, Alias}, logic::{binary, negate, cast}, fold::{ConstValue, fold, fold_stmt}};
+
+/// Second pass, resolves names and gives every expression its type
+impl Module {
+    /// Checks the statements of the module in order, a statement with an error is reported and the next one is checked
+    pub fn check(&mut self, stmts: &mut [Stmt]) -> Vec<PhoenixError> {
+        let mut errors = vec![];
+        for stmt in stmts.iter_mut() {
+            if let Err(err) = self.check_stmt(stmt) {
+                errors.push(err);
+                // Blocks the error left open are closed
+                self.scope_depth = 0;
+                self.locals.retain(|local| local.depth == 0);
+            }
+        }
+        errors.extend(self.infer.finish());
+
+        // Types are only final once the whole module has been seen, so are the values of constant operations
+        stmts.iter_mut().for_each(|stmt| stmt.visit_mut(&mut |expr| expr.ty = self.infer.resolve(expr.ty)));
+        errors.extend(stmts.iter_mut().filter_map(|stmt| fold_stmt(stmt).err()));
+        errors
+    }
+
+    /// Type the statement leaves on the stack, `Void` if none
+    fn check_stmt(&mut self, stmt: &mut Stmt) -> Result<Type, PhoenixError> {
+        match stmt {
+            Stmt::Expr(expr) => self.check_expr(expr),
+            Stmt::Print(expr) => {
+                if self.check_expr(expr)? == Type::Void {
+                    return Err(PhoenixError::Compile { id: CompErrID::TypeError, span: expr.span, msg: String::from("print statement requires a non-void expression") })
+                }
+                Ok(Type::Void)
+            }
+            Stmt::Alias { name, span, ty } => {
+                ty.ty = self.resolve_type(ty)?.ty;
+                if Type::from_str(name).is_ok() || self.aliases.contains_key(name) {
+                    return Err(PhoenixError::Compile { id: CompErrID::DuplicateSymbol, span: *span,
+                        msg: format!("Type '{name}' is already defined") })
+                }
+                self.aliases.insert(name.clone(), Alias { ty: ty.ty });
+                Ok(Type::Void)
+            }
+            Stmt::Const { name, span, ty, value } => {
+                if self.consts.contains_key(name) {
+                    return Err(PhoenixError::Compile { id: CompErrID::DuplicateSymbol, span: *span,
+                        msg: format!("Constant '{name}' is already defined") })
+                }
+                let req_ty = self.resolve_type(ty)?;
+                ty.ty = req_ty.ty;
+                let value_ty = self.check_expr(value)?;
+                self.infer.unify(req_ty.ty, value_ty).map_err(|(_, value_ty)| PhoenixError::Compile { id: CompErrID::TypeError, span: ty.span,
+                    msg: format!("Expected value of type '{}' as specified, type '{}' was instead provided", req_ty, value_ty) })?;
+
+                // The value is needed by the uses that follow, so its literals take their default type right away
+                value.visit_mut(&mut |expr| { let ty = self.infer.default(expr.ty); expr.ty = self.infer.resolve(ty) });
+                fold(value)?;
+                let constant = ConstValue::of(value).ok_or_else(|| PhoenixError::Compile { id: CompErrID::NotConstant, span: value.span,
+                    msg: format!("Value of constant '{name}' is not known at compile time") })?;
+                self.consts.insert(name.clone(), (ty.ty, constant));
+                Ok(Type::Void)
+            }
+        }
+    }
+
+    pub fn check_expr(&mut self, expr: &mut Expr) -> Result<Type, PhoenixError> {
+        let span = expr.span;
+        // Constants are replaced by their value
+        if let ExprKind::Var { name, .. } = &expr.kind {
+            if let Some((ty, value)) = self.resolve_const(name) {
+                let ty = *ty;
+                (expr.kind, expr.ty) = (value.clone().into_kind(ty), ty);
+                return Ok(ty)
+            }
+        }
+        let ty = match &mut expr.kind {
+            ExprKind::Bool(_) => Type::Bool,
+            ExprKind::Str(_) => Type::Str,
+            ExprKind::Char(_) => Type::Char,
+            ExprKind::Number { value, suffix: None, lexeme } => self.infer.lit(*value, span, lexeme),
+            ExprKind::Number { value, suffix: Some(ty), lexeme } => {
+                if ty.lit_const(*value).is_none() {
+                    return Err(PhoenixError::Compile { id: CompErrID::TypeError, span, msg: format!("Literal '{lexeme}' does not fit in type '{ty}'") })
+                }
+                *ty
+            }
+            ExprKind::Interp(parts) => {
+                for part in parts.iter_mut() {
+                    match self.check_expr(part).map(|ty| self.infer.shallow(ty))? {
+                        ty if ty.is_numeric() || matches!(ty, Type::Str | Type::Char | Type::Bool | Type::Var(_)) => {}
+                        ty => return Err(PhoenixError::Compile { id: CompErrID::TypeError, span: part.span, msg: format!("Type '{ty}' cannot be interpolated in a Str") }),
+                    }
+                }
+                if parts.len() > u8::MAX as usize {
+                    return Err(PhoenixError::Compile { id: CompErrID::InvalidSymbol, span,
+                        msg: format!("Interpolated string has {} parts, at most 255 are allowed", parts.len()) })
+                }
+                Type::Str
+            }
+            ExprKind::Var { name, binding } => { let (ty, bound) = self.get_symbol(name, span)?; *binding = bound; ty }
+            ExprKind::Let { name, ty, value, binding, .. } => { *binding = self.check_let(name, ty.as_mut(), value)?; Type::Void }
+            ExprKind::Assign { name, name_span, op, op_span, value, binding } => {
+                *binding = self.check_assign(name, *name_span, op.map(|op| (op, *op_span)), value)?; Type::Void
+            }
+            ExprKind::Neg { op_span, expr } => {
+                let rht = self.check_expr(expr)?;
+                negate(&mut self.infer, (rht, expr.span), *op_span)?
+            }
+            ExprKind::Binary { op, op_span, lhs, rhs } => {
+                let lht = self.check_expr(lhs)?;
+                let rht = self.check_expr(rhs)?;
+                binary(&mut self.infer, *op, (lht, lhs.span), (rht, rhs.span), *op_span)?
+            }
+            ExprKind::Cast { op_span, expr, to } => {
+                let literal = match &expr.kind {
+                    ExprKind::Number { suffix: None, .. } => true,
+                    ExprKind::Neg { expr, .. } => matches!(expr.kind, ExprKind::Number { suffix: None, .. }),
+                    _ => false,
+                };
+                let from = self.check_expr(expr)?;
+                let parsed = self.resolve_type(to)?;
+                to.ty = parsed.ty;
+                cast(&mut self.infer, (from, expr.span), &parsed, *op_span, literal)?
+            }
+            ExprKind::Block { stmts, locals, void } => {
+                self.scope_depth += 1;
+                let mut block_ty = Type::Void;
+                for stmt in stmts.iter_mut() { block_ty = self.check_stmt(stmt)? }
+                if *void { block_ty = Type::Void }
+
+                self.scope_depth -= 1;
+                while self.locals.last().is_some_and(|local| local.depth > self.scope_depth) { self.locals.pop(); *locals += 1 }
+                block_ty
+            }
+        };
+        expr.ty = ty;
+        Ok(ty)
+    }
+}
+
+impl Stmt {
+    /// Calls `f` on every expression of the statement, children before their parent
+    fn visit_mut(&mut self, f: &mut impl FnMut(&mut Expr)) {
+        match self { Stmt::Expr(expr) | Stmt::Print(expr) | Stmt::Const { value: expr, .. } => expr.visit_mut(f), Stmt::Alias { .. } => {} }
+    }
+}
+
+impl Expr {
+    fn visit_mut(&mut self, f: &mut impl FnMut(&mut Expr)) {
+        match &mut self.kind {
+            ExprKind::Interp(parts) => parts.iter_mut().for_each(|part| part.visit_mut(f)),
+            ExprKind::Let { value, .. } | ExprKind::Assign { value, .. } => value.visit_mut(f),
+            ExprKind::Neg { expr, .. } | ExprKind::Cast { expr, .. } => expr.visit_mut(f),
+            ExprKind::Binary { lhs, rhs, .. } => { lhs.visit_mut(f); rhs.visit_mut(f) }
+            ExprKind::Block { stmts, .. } => stmts.iter_mut().for_each(|stmt| stmt.visit_mut(f)),
+            _ => {}
+        }
+        f(self)
+    }
+}
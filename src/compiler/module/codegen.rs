@@ -0,0 +1,117 @@
+use crate::{compiler::chunk::{Chunk, Const}, flamebytecode::FBOpCode::{self, *}};
+
+use super::{Module, ast::{Stmt, Expr, ExprKind, Binding, BinOp}, types::Type};
+
+/// Last pass, writes the bytecode of a checked syntax tree
+impl Module {
+    pub fn codegen(&mut self, stmts: &[Stmt]) {
+        let chunk = self.chunk.as_mut().unwrap();
+        for stmt in stmts {
+            emit_stmt(chunk, stmt);
+            // Values of expression statements are dropped, there is nothing to use them
+            if stmt_ty(stmt) != Type::Void { chunk.write_op(OpPop) }
+        }
+    }
+}
+
+/// Type a statement leaves on the stack
+fn stmt_ty(stmt: &Stmt) -> Type {
+    match stmt { Stmt::Expr(expr) => expr.ty, Stmt::Print(_) | Stmt::Alias { .. } | Stmt::Const { .. } => Type::Void }
+}
+
+fn emit_stmt(chunk: &mut Chunk, stmt: &Stmt) {
+    match stmt {
+        Stmt::Expr(expr) => emit(chunk, expr),
+        Stmt::Print(expr) => { emit(chunk, expr); chunk.write_op(OpPrint) }
+        Stmt::Alias { .. } | Stmt::Const { .. } => {}
+    }
+}
+
+fn emit(chunk: &mut Chunk, expr: &Expr) {
+    match &expr.kind {
+        ExprKind::Bool(v) => chunk.write_op(if *v { OpTrue } else { OpFalse }),
+        ExprKind::Number { value, .. } => chunk.write_const(expr.ty.lit_const(*value).unwrap()),
+        ExprKind::Str(v) => chunk.write_const(Const::String((**v).into())),
+        ExprKind::Char(v) => chunk.write_const(Const::Char(*v)),
+        ExprKind::Interp(parts) => {
+            parts.iter().for_each(|part| emit(chunk, part));
+            chunk.write_op(OpFormat); chunk.write(&[parts.len() as u8]);
+        }
+        ExprKind::Var { name, binding } => get(chunk, name, *binding),
+        // A new local is the value left on the stack by its initializer
+        ExprKind::Let { name, value, binding, .. } => {
+            emit(chunk, value);
+            if *binding == Binding::Global { set(chunk, name, *binding) }
+        }
+        ExprKind::Assign { name, op, value, binding, .. } => {
+            if op.is_some() { get(chunk, name, *binding) }
+            emit(chunk, value);
+            // Only Str accepts an operand of another type, a Char
+            if let Some(op) = op { chunk.write_op(bin_opcode(*op, if value.ty == Type::Char { Type::Str } else { value.ty }, value.ty)) }
+            set(chunk, name, *binding);
+        }
+        ExprKind::Neg { expr, .. } => { emit(chunk, expr); chunk.write_op(OpNeg) }
+        ExprKind::Binary { op, lhs, rhs, .. } => { emit(chunk, lhs); emit(chunk, rhs); chunk.write_op(bin_opcode(*op, lhs.ty, rhs.ty)) }
+        ExprKind::Cast { expr: inner, .. } => {
+            emit(chunk, inner);
+            if inner.ty != expr.ty { chunk.write_op(OpCast); chunk.write(&[expr.ty.cast_ty().unwrap() as u8]) }
+        }
+        ExprKind::Block { stmts, locals, .. } => {
+            let mut block_ty = Type::Void;
+            for stmt in stmts {
+                if block_ty != Type::Void { chunk.write_op(OpPop) }
+                emit_stmt(chunk, stmt);
+                block_ty = stmt_ty(stmt);
+            }
+            if block_ty != Type::Void && expr.ty == Type::Void { chunk.write_op(OpPop) }
+
+            // The value of the block stays on top, its locals are dropped from under it
+            let mut locals = *locals as usize;
+            while locals > 0 {
+                let n = locals.min(u8::MAX as usize);
+                if expr.ty == Type::Void { (0..n).for_each(|_| chunk.write_op(OpPop)) } else { chunk.write_op(OpPopUnder); chunk.write(&[n as u8]) }
+                locals -= n;
+            }
+        }
+    }
+}
+
+fn get(chunk: &mut Chunk, name: &str, binding: Binding) {
+    match binding {
+        Binding::Local(addr) => { chunk.write_op(OpLocGet); chunk.write(&addr.to_le_bytes()[..3]) }
+        _ => {
+            chunk.write_op(OpGlobGet);
+            let name_const = chunk.add_get_const(Const::String(name.into()));
+            chunk.write(&name_const.to_le_bytes()[..3]);
+        }
+    }
+}
+
+fn set(chunk: &mut Chunk, name: &str, binding: Binding) {
+    match binding {
+        Binding::Local(addr) => { chunk.write_op(OpLocSet); chunk.write(&addr.to_le_bytes()[..3]) }
+        _ => {
+            let name_const = chunk.add_get_const(Const::String(name.into()));
+            chunk.write_op(OpGlobSet);
+            chunk.write(&name_const.to_le_bytes()[..3]);
+        }
+    }
+}
+
+/// Opcode of the operation on operands of these types, specialized for the most common ones so the VM does not dispatch on them
+fn bin_opcode(op: BinOp, lht: Type, rht: Type) -> FBOpCode {
+    match (op, lht, rht) {
+        (BinOp::Add, Type::Str, Type::Str) => return OpConcat,
+        (BinOp::Add, Type::Str, Type::Char) => return OpConcatChar,
+        (BinOp::Add, Type::Int, _) => return OpAddInt, (BinOp::Sub, Type::Int, _) => return OpSubInt,
+        (BinOp::Mul, Type::Int, _) => return OpMulInt, (BinOp::Div, Type::Int, _) => return OpDivInt,
+        (BinOp::Add, Type::Dec, _) => return OpAddDec, (BinOp::Sub, Type::Dec, _) => return OpSubDec,
+        (BinOp::Mul, Type::Dec, _) => return OpMulDec, (BinOp::Div, Type::Dec, _) => return OpDivDec,
+        _ => {}
+    }
+    match op {
+        BinOp::Add => OpAdd, BinOp::Sub => OpSub, BinOp::Mul => OpMul, BinOp::Div => OpDiv,
+        BinOp::WrapAdd => OpWrapAdd, BinOp::WrapSub => OpWrapSub, BinOp::WrapMul => OpWrapMul,
+        BinOp::SatAdd => OpSatAdd, BinOp::SatSub => OpSatSub, BinOp::SatMul => OpSatMul,
+    }
+}
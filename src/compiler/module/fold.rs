@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use crate::{decimal::Decimal, error::{PhoenixError, CompErrID}, source::Span};
+
+use super::{ast::{Stmt, Expr, ExprKind, BinOp}, types::Type, infer::Lit};
+
+/// Value of an expression known at compile time
+#[derive(Clone, Debug)]
+pub enum ConstValue { Bool(bool), Num(Lit), Str(Arc<str>), Char(char) }
+
+impl ConstValue {
+    /// Value of a literal, `None` for any other expression and for a number that does not fit in its type
+    pub fn of(expr: &Expr) -> Option<Self> {
+        match &expr.kind {
+            ExprKind::Bool(v) => Some(Self::Bool(*v)),
+            ExprKind::Number { value, .. } => expr.ty.lit_const(*value).map(|_| Self::Num(*value)),
+            ExprKind::Str(v) => Some(Self::Str(v.clone())),
+            ExprKind::Char(v) => Some(Self::Char(*v)),
+            _ => None,
+        }
+    }
+
+    /// Literal expression of type `ty` holding the value
+    pub fn into_kind(self, ty: Type) -> ExprKind {
+        match self {
+            Self::Bool(v) => ExprKind::Bool(v),
+            Self::Num(value) => ExprKind::Number { value, suffix: Some(ty), lexeme: Self::Num(value).text(ty).into() },
+            Self::Str(v) => ExprKind::Str(v),
+            Self::Char(v) => ExprKind::Char(v),
+        }
+    }
+
+    /// The value as the VM prints it
+    fn text(&self, ty: Type) -> String {
+        match self {
+            Self::Bool(v) => v.to_string(),
+            Self::Str(v) => v.to_string(),
+            Self::Char(v) => v.to_string(),
+            Self::Num(value) => match normalize(*value, ty) {
+                Some(Lit::Int(v)) => v.to_string(),
+                Some(Lit::Dec(v)) => v.to_string(),
+                Some(Lit::Float(v)) if ty == Type::F32 => format!("{:?}", v as f32),
+                Some(Lit::Float(v)) => format!("{v:?}"),
+                None => String::new(),
+            }
+        }
+    }
+}
+
+/// Replaces every operation on constant operands with its result
+pub fn fold_stmt(stmt: &mut Stmt) -> Result<(), PhoenixError> {
+    match stmt {
+        Stmt::Expr(expr) | Stmt::Print(expr) => fold(expr),
+        // Constants are folded as soon as they are checked
+        Stmt::Alias { .. } | Stmt::Const { .. } => Ok(()),
+    }
+}
+
+/// Folds the expression once its type is final, failing like the VM would, such as on overflow
+pub fn fold(expr: &mut Expr) -> Result<(), PhoenixError> {
+    let (ty, span) = (expr.ty, expr.span);
+    let value = match &mut expr.kind {
+        ExprKind::Interp(parts) => {
+            let mut str = String::new();
+            for part in parts.iter_mut() {
+                fold(part)?;
+                match ConstValue::of(part) { Some(value) => str.push_str(&value.text(part.ty)), None => return Ok(()) }
+            }
+            ConstValue::Str(str.into())
+        }
+        ExprKind::Let { value, .. } | ExprKind::Assign { value, .. } => return fold(value),
+        ExprKind::Block { stmts, .. } => return stmts.iter_mut().try_for_each(fold_stmt),
+        ExprKind::Neg { expr: inner, .. } => {
+            fold(inner)?;
+            let Some(ConstValue::Num(value)) = ConstValue::of(inner) else { return Ok(()) };
+            match normalize(value, ty) {
+                Some(Lit::Int(v)) => ConstValue::Num(Lit::Int(in_bounds(v.checked_neg(), ty).ok_or_else(|| overflow("negation", ty, span))?)),
+                Some(Lit::Dec(v)) => ConstValue::Num(Lit::Dec(v.checked_neg().ok_or_else(|| overflow("negation", ty, span))?)),
+                Some(Lit::Float(v)) => ConstValue::Num(Lit::Float(-v)),
+                None => return Ok(()),
+            }
+        }
+        ExprKind::Binary { op, lhs, rhs, .. } => {
+            fold(lhs)?; fold(rhs)?;
+            let (Some(lht), Some(rht)) = (ConstValue::of(lhs), ConstValue::of(rhs)) else { return Ok(()) };
+            match binary(*op, lht, rht, ty, span)? { Some(value) => value, None => return Ok(()) }
+        }
+        // Only casts to the type the value already has, which are no conversion at all
+        ExprKind::Cast { expr: inner, .. } => {
+            fold(inner)?;
+            match ConstValue::of(inner) { Some(value) if inner.ty == ty => value, _ => return Ok(()) }
+        }
+        _ => return Ok(()),
+    };
+    expr.kind = value.into_kind(ty);
+    Ok(())
+}
+
+/// Numeric literal in the representation of its type, `Int` for integers, `Dec` for Dec and `Float` for binary floats
+fn normalize(value: Lit, ty: Type) -> Option<Lit> {
+    Some(match (value, ty) {
+        (Lit::Int(v), ty) if ty.is_int() => Lit::Int(v),
+        (Lit::Int(v), Type::Dec) => Lit::Dec(Decimal::from_int(v)),
+        (Lit::Dec(v), Type::Dec) => Lit::Dec(v),
+        (Lit::Int(v), Type::F32) => Lit::Float(v as f32 as f64),
+        (Lit::Dec(v), Type::F32) => Lit::Float(v.to_f64() as f32 as f64),
+        (Lit::Float(v), Type::F32) => Lit::Float(v as f32 as f64),
+        (Lit::Int(v), Type::F64) => Lit::Float(v as f64),
+        (Lit::Dec(v), Type::F64) => Lit::Float(v.to_f64()),
+        (Lit::Float(v), Type::F64) => Lit::Float(v),
+        _ => return None,
+    })
+}
+
+fn binary(op: BinOp, lht: ConstValue, rht: ConstValue, ty: Type, span: Span) -> Result<Option<ConstValue>, PhoenixError> {
+    let what = match op {
+        BinOp::Add | BinOp::WrapAdd | BinOp::SatAdd => "addition",
+        BinOp::Sub | BinOp::WrapSub | BinOp::SatSub => "subtraction",
+        BinOp::Mul | BinOp::WrapMul | BinOp::SatMul => "multiplication",
+        BinOp::Div => "division",
+    };
+    let (a, b) = match (lht, rht) {
+        (ConstValue::Str(a), ConstValue::Str(b)) if op == BinOp::Add => return Ok(Some(ConstValue::Str(format!("{a}{b}").into()))),
+        (ConstValue::Str(a), ConstValue::Char(b)) if op == BinOp::Add => return Ok(Some(ConstValue::Str(format!("{a}{b}").into()))),
+        (ConstValue::Num(a), ConstValue::Num(b)) => match (normalize(a, ty), normalize(b, ty)) { (Some(a), Some(b)) => (a, b), _ => return Ok(None) },
+        _ => return Ok(None),
+    };
+    // Binary floats divided by zero give an infinity as they do at runtime
+    let zero = match b { Lit::Int(b) => b == 0, Lit::Dec(b) => b.is_zero(), Lit::Float(_) => false };
+    if op == BinOp::Div && zero {
+        return Err(PhoenixError::Compile { id: CompErrID::DivisionByZero, span, msg: format!("Constant division by zero") })
+    }
+
+    let value = match (a, b) {
+        (Lit::Int(a), Lit::Int(b)) => {
+            let Some((min, max)) = ty.int_bounds() else { return Ok(None) };
+            let exact = match op {
+                BinOp::Add | BinOp::WrapAdd | BinOp::SatAdd => a.checked_add(b),
+                BinOp::Sub | BinOp::WrapSub | BinOp::SatSub => a.checked_sub(b),
+                BinOp::Mul | BinOp::WrapMul | BinOp::SatMul => a.checked_mul(b),
+                BinOp::Div => a.checked_div(b),
+            };
+            Lit::Int(match op {
+                // Wrapping in i128 keeps the result right modulo the width of any smaller type
+                BinOp::WrapAdd | BinOp::WrapSub | BinOp::WrapMul => {
+                    let wrapped = match op { BinOp::WrapAdd => a.wrapping_add(b), BinOp::WrapSub => a.wrapping_sub(b), _ => a.wrapping_mul(b) };
+                    wrapped.wrapping_sub(min).rem_euclid(max - min + 1) + min
+                }
+                BinOp::SatAdd | BinOp::SatSub | BinOp::SatMul => exact.unwrap_or(if (a < 0) != (b < 0) { i128::MIN } else { i128::MAX }).clamp(min, max),
+                _ => in_bounds(exact, ty).ok_or_else(|| overflow(what, ty, span))?,
+            })
+        }
+        (Lit::Dec(a), Lit::Dec(b)) => Lit::Dec(match op {
+            BinOp::Add => a.checked_add(b), BinOp::Sub => a.checked_sub(b), BinOp::Mul => a.checked_mul(b), BinOp::Div => a.checked_div(b),
+            _ => return Ok(None),
+        }.ok_or_else(|| overflow(what, ty, span))?),
+        // Binary floats follow IEEE 754 like at runtime, they never fail
+        (Lit::Float(a), Lit::Float(b)) if ty == Type::F32 => {
+            let (a, b) = (a as f32, b as f32);
+            Lit::Float(match op { BinOp::Add => a + b, BinOp::Sub => a - b, BinOp::Mul => a * b, BinOp::Div => a / b, _ => return Ok(None) } as f64)
+        }
+        (Lit::Float(a), Lit::Float(b)) => Lit::Float(match op { BinOp::Add => a + b, BinOp::Sub => a - b, BinOp::Mul => a * b, BinOp::Div => a / b, _ => return Ok(None) }),
+        _ => return Ok(None),
+    };
+    Ok(Some(ConstValue::Num(value)))
+}
+
+/// Integer result if it fits in `ty`
+fn in_bounds(value: Option<i128>, ty: Type) -> Option<i128> {
+    let (min, max) = ty.int_bounds()?;
+    value.filter(|value| (min..=max).contains(value))
+}
+
+fn overflow(what: &str, ty: Type, span: Span) -> PhoenixError {
+    PhoenixError::Compile { id: CompErrID::ConstantOverflow, span, msg: format!("Constant {what} overflowed type '{ty}'") }
+}
@@ -0,0 +1,168 @@
+use crate::{decimal::Decimal, error::{PhoenixError, CompErrID}, source::Span};
+
+use super::types::{Type, ParsedType};
+
+/// How constrained an inference variable is, a variable can only move to a stricter kind.
+/// `IntLit` can still become any number while `Integral` has been used where only integers are allowed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VarKind { Any, IntLit, DecLit, Integral }
+
+impl VarKind {
+    /// Kind satisfying both constraints, `None` if no type can
+    fn meet(self, other: Self) -> Option<Self> {
+        match (self, other) {
+            (a, b) if a == b => Some(a),
+            (VarKind::Any, k) | (k, VarKind::Any) => Some(k),
+            (VarKind::IntLit, k) | (k, VarKind::IntLit) => Some(k),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VarId { pub id: u32, pub kind: VarKind }
+
+/// Value of a numeric literal, its constant is only built once its type is known.
+/// `Float` is only used for literals with an `f32` or `f64` suffix, whose exponent may be far outside of what Dec can hold
+#[derive(Clone, Copy, Debug)]
+pub enum Lit { Int(i128), Dec(Decimal), Float(f64) }
+
+/// Constraint table of a function body: variables are unified as expressions are checked
+/// and only resolved once the whole body has been seen, so later uses can decide earlier types.
+pub struct Infer {
+    parent: Vec<u32>,
+    kind: Vec<VarKind>,
+    bound: Vec<Option<Type>>,
+    origin: Vec<(Span, String)>,
+    /// Literals whose final type is still unknown, checked to fit in it once it is
+    lits: Vec<(u32, Lit)>,
+    /// Casts of operands whose type is still unknown, checked to be allowed once it is
+    casts: Vec<(Type, ParsedType, Span)>,
+    /// Negations of operands whose type is still unknown, checked not to be unsigned once it is
+    negations: Vec<(Type, Span)>,
+}
+
+impl Infer {
+    pub fn new() -> Self { Self { parent: vec![], kind: vec![], bound: vec![], origin: vec![], lits: vec![], casts: vec![], negations: vec![] } }
+
+    /// New variable, `origin` names the expression it stands for in "cannot infer" errors
+    pub fn fresh(&mut self, kind: VarKind, pos: Span, origin: String) -> Type {
+        let id = self.parent.len() as u32;
+        self.parent.push(id); self.kind.push(kind); self.bound.push(None); self.origin.push((pos, origin));
+        Type::Var(VarId { id, kind })
+    }
+
+    /// Unsuffixed literal, [`Infer::finish`] reports it if it does not fit in its final type
+    pub fn lit(&mut self, value: Lit, pos: Span, lexeme: &str) -> Type {
+        let (kind, name) = match value { Lit::Int(_) => (VarKind::IntLit, "integer"), Lit::Dec(_) | Lit::Float(_) => (VarKind::DecLit, "decimal") };
+        let ty = self.fresh(kind, pos, format!("{name} literal '{lexeme}'"));
+        let Type::Var(var) = ty else { unreachable!() };
+        self.lits.push((var.id, value)); ty
+    }
+
+    /// Cast of an operand whose type is not known yet, [`Infer::finish`] reports it if the conversion is not allowed
+    pub fn cast(&mut self, from: Type, to: ParsedType, op: Span) { self.casts.push((from, to, op)) }
+
+    /// Negation of an operand whose type is not known yet, [`Infer::finish`] reports it if the type is unsigned
+    pub fn negate(&mut self, ty: Type, op: Span) { self.negations.push((ty, op)) }
+
+    fn find(&mut self, id: u32) -> u32 {
+        let parent = self.parent[id as usize];
+        if parent == id { return id }
+        let root = self.find(parent); self.parent[id as usize] = root; root
+    }
+
+    /// Concrete type if already known, otherwise the representative variable with its current kind
+    pub fn shallow(&mut self, ty: Type) -> Type {
+        match ty {
+            Type::Var(var) => {
+                let root = self.find(var.id);
+                self.bound[root as usize].unwrap_or(Type::Var(VarId { id: root, kind: self.kind[root as usize] }))
+            }
+            ty => ty,
+        }
+    }
+
+    fn accepts(kind: VarKind, ty: Type) -> bool {
+        match kind {
+            VarKind::Any => ty != Type::Void,
+            VarKind::IntLit => ty.is_int() || ty.is_fractional(),
+            VarKind::DecLit => ty.is_fractional(),
+            VarKind::Integral => ty.is_int(),
+        }
+    }
+
+    /// Makes both types equal, on failure returns both sides as they were known at that point
+    pub fn unify(&mut self, a: Type, b: Type) -> Result<Type, (Type, Type)> {
+        match (self.shallow(a), self.shallow(b)) {
+            (Type::Var(x), Type::Var(y)) if x.id == y.id => Ok(Type::Var(x)),
+            (Type::Var(x), Type::Var(y)) => {
+                let Some(kind) = x.kind.meet(y.kind) else { return Err((Type::Var(x), Type::Var(y))) };
+                self.parent[y.id as usize] = x.id; self.kind[x.id as usize] = kind;
+                Ok(Type::Var(VarId { id: x.id, kind }))
+            }
+            (Type::Var(var), ty) | (ty, Type::Var(var)) if Self::accepts(var.kind, ty) => { self.bound[var.id as usize] = Some(ty); Ok(ty) }
+            (a, b) if a == b => Ok(a),
+            (a, b) => Err((a, b)),
+        }
+    }
+
+    /// Binds a still open variable to the type its literals default to
+    pub fn default(&mut self, ty: Type) -> Type {
+        match self.shallow(ty) {
+            Type::Var(VarId { id, kind: kind @ (VarKind::IntLit | VarKind::DecLit | VarKind::Integral) }) => {
+                let ty = if kind == VarKind::DecLit { Type::Dec } else { Type::Int };
+                self.bound[id as usize] = Some(ty); ty
+            }
+            ty => ty,
+        }
+    }
+
+    /// Final type of a checked expression, only valid after [`Infer::finish`]
+    pub fn resolve(&mut self, ty: Type) -> Type {
+        match self.shallow(ty) { Type::Var(_) => Type::Void, ty => ty }
+    }
+
+    /// Resolves every variable and checks that the literals that depended on them fit in their type
+    pub fn finish(&mut self) -> Vec<PhoenixError> {
+        let mut errors = vec![];
+        for id in 0..self.parent.len() as u32 {
+            let root = self.find(id);
+            if self.bound[root as usize].is_some() { continue }
+            match self.default(Type::Var(VarId { id: root, kind: self.kind[root as usize] })) {
+                Type::Var(_) => {
+                    let (span, origin) = &self.origin[root as usize];
+                    errors.push(PhoenixError::Compile { id: CompErrID::TypeError, span: *span,
+                        msg: format!("Type of {origin} cannot be inferred, must be specified") });
+                    self.bound[root as usize] = Some(Type::Void);
+                }
+                _ => {}
+            }
+        }
+
+        for (id, value) in std::mem::take(&mut self.lits) {
+            let root = self.find(id);
+            let ty = self.bound[root as usize].unwrap();
+            if ty.lit_const(value).is_none() {
+                let (span, origin) = &self.origin[id as usize];
+                errors.push(PhoenixError::Compile { id: CompErrID::TypeError, span: *span,
+                    msg: format!("The {origin} does not fit in type '{ty}'") });
+            }
+        }
+
+        for (from, to, op) in std::mem::take(&mut self.casts) {
+            // Operands that could not be inferred were already reported
+            let from = self.resolve(from);
+            if from != Type::Void && !from.castable_to(to.ty) {
+                errors.push(PhoenixError::Compile { id: CompErrID::TypeError, span: op, msg: format!("Type '{from}' cannot be cast to '{to}'") });
+            }
+        }
+        for (ty, op) in std::mem::take(&mut self.negations) {
+            let ty = self.resolve(ty);
+            if ty.is_unsigned() {
+                errors.push(PhoenixError::Compile { id: CompErrID::TypeError, span: op, msg: format!("Type '{ty}' has no 'negate' function") });
+            }
+        }
+        errors
+    }
+}
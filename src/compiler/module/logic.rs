@@ -1,68 +1,102 @@
-use crate::{compiler::{token::{Token, TokenType, TokenType::*}, chunk::{Chunk, Const}, Compiler}, error::{PhoenixError, CompErrID}, flamebytecode::FBOpCode};
-use std::{string::String, sync::Arc};
+use crate::{error::{PhoenixError, CompErrID}, source::Span};
+use std::string::String;
 
-use super::{types::{Type, parse_type}, Module, Local};
+use super::{types::{Type, ParsedType}, infer::{Infer, VarKind}, ast::BinOp};
 pub mod symbols;
 
 
 #[inline(always)]
-fn type_error(ty: Type, acceptable: &[Type], row: u16, col: u16, msg: String) -> Result<Type, PhoenixError> { 
+fn type_error(ty: Type, acceptable: &[Type], span: Span, msg: String) -> Result<Type, PhoenixError> { 
     if acceptable.contains(&ty) { Ok(ty) }
-    else { Err(PhoenixError::Compile { id: CompErrID::TypeError, row, col, msg }) }
+    else { Err(PhoenixError::Compile { id: CompErrID::TypeError, span, msg }) }
 }
 
-pub fn plus(chunk: &mut Chunk, lht: (Type, (u16, u16)), rht: (Type, (u16, u16)), op: &Token) -> Result<Type, PhoenixError> {
-    match lht.0 {
-        ty @ (Type::Int | Type::Dec) => {
-            let ret_ty = type_error(rht.0, &[ty], lht.1.0, lht.1.1, format!("Type '{}' cannot be added to a {}", rht.0, ty))?;
-            chunk.write_op(FBOpCode::OpAdd); Ok(ret_ty)
+/// Type of a binary operation, `op_span` locates the operator
+pub fn binary(infer: &mut Infer, op: BinOp, lht: (Type, Span), rht: (Type, Span), op_span: Span) -> Result<Type, PhoenixError> {
+    match op {
+        BinOp::Add => plus(infer, lht, rht, op_span),
+        BinOp::Sub => minus(infer, lht, rht, op_span),
+        BinOp::Mul => star(infer, lht, rht, op_span),
+        BinOp::Div => slash(infer, lht, rht, op_span),
+        BinOp::WrapAdd => wrapping_plus(infer, lht, rht, op_span),
+        BinOp::WrapSub => wrapping_minus(infer, lht, rht, op_span),
+        BinOp::WrapMul => wrapping_star(infer, lht, rht, op_span),
+        BinOp::SatAdd => saturating_plus(infer, lht, rht, op_span),
+        BinOp::SatSub => saturating_minus(infer, lht, rht, op_span),
+        BinOp::SatMul => saturating_star(infer, lht, rht, op_span),
+    }
+}
+
+pub fn plus(infer: &mut Infer, lht: (Type, Span), rht: (Type, Span), op: Span) -> Result<Type, PhoenixError> {
+    let (l, r) = (infer.shallow(lht.0), infer.shallow(rht.0));
+    match l {
+        ty if ty.is_numeric() => {
+            infer.unify(l, r).or_else(|_| type_error(r, &[], lht.1, format!("Type '{}' cannot be added to a {}", r, l)))
         }
         Type::Str => {
-            let ret_ty = match rht.0 {
-                Type::Str | Type::Char => Type::Str,
-                _ => type_error(rht.0, &[], lht.1.0, lht.1.1, format!("Cannot concat Str with {}", rht.0))?,
-            };
-            chunk.write_op(FBOpCode::OpAdd); Ok(ret_ty)
+            match r {
+                Type::Str | Type::Char => Ok(Type::Str),
+                _ => type_error(r, &[], lht.1, format!("Cannot concat Str with {}", r)),
+            }
         }
-        ty => type_error(rht.0, &[], op.pos.0, op.pos.1, format!("Type '{}' has not 'plus' function", ty)),
+        ty => type_error(r, &[], op, format!("Type '{}' has not 'plus' function", ty)),
     }
 }
 
 macro_rules! int_float_arithmetics {
-    ($name:ident, $name_str:literal, $op:expr, $verb:literal) => {
-        pub fn $name(chunk: &mut Chunk, lht: (Type, (u16, u16)), rht: (Type, (u16, u16)), op: &Token) -> Result<Type, PhoenixError> {
-            match lht.0 {
-                ty @ (Type::Int | Type::Dec) => {
-                    let ret_ty = type_error(rht.0, &[ty], lht.1.0, lht.1.1, format!("Type '{}' cannot be {} to a {}", rht.0, $verb, ty))?;
-                    chunk.write_op($op); Ok(ret_ty)
-                }
-                ty => type_error(rht.0, &[], op.pos.0, op.pos.1, format!("Type '{}' has no '{}' function", ty, $name_str)),
+    ($name:ident, $name_str:literal, $verb:literal) => {
+        pub fn $name(infer: &mut Infer, lht: (Type, Span), rht: (Type, Span), op: Span) -> Result<Type, PhoenixError> {
+            let (l, r) = (infer.shallow(lht.0), infer.shallow(rht.0));
+            match l {
+                ty if ty.is_numeric() => infer.unify(l, r).or_else(|_| type_error(r, &[], lht.1, format!("Type '{}' cannot be {} to a {}", r, $verb, l))),
+                ty => type_error(r, &[], op, format!("Type '{}' has no '{}' function", ty, $name_str)),
+            }
+        }
+    };
+}
+int_float_arithmetics!(minus, "minus", "subtracted");
+int_float_arithmetics!(star, "mul", "multiplied");
+int_float_arithmetics!(slash, "div", "divided");
+
+/// Wrapping and saturating operators, only defined on integers
+macro_rules! int_arithmetics {
+    ($name:ident, $name_str:literal, $verb:literal) => {
+        pub fn $name(infer: &mut Infer, lht: (Type, Span), rht: (Type, Span), op: Span) -> Result<Type, PhoenixError> {
+            let (l, r) = (infer.shallow(lht.0), infer.shallow(rht.0));
+            let int = infer.fresh(VarKind::Integral, op, format!("operand of '{}'", $name_str));
+            match infer.unify(l, int) {
+                Ok(l) => infer.unify(l, r).or_else(|(l, r)| type_error(r, &[], lht.1, format!("Type '{}' cannot be {} to a {}", r, $verb, l))),
+                Err(_) => type_error(l, &[], op, format!("Type '{}' has no '{}' function", l, $name_str)),
             }
         }
     };
 }
-int_float_arithmetics!(minus, "minus", FBOpCode::OpSub, "subtracted");
-int_float_arithmetics!(star, "mul", FBOpCode::OpMul, "multiplied");
-int_float_arithmetics!(slash, "div", FBOpCode::OpDiv, "divided");
+int_arithmetics!(wrapping_plus, "wrapping_plus", "added");
+int_arithmetics!(wrapping_minus, "wrapping_minus", "subtracted");
+int_arithmetics!(wrapping_star, "wrapping_mul", "multiplied");
+int_arithmetics!(saturating_plus, "saturating_plus", "added");
+int_arithmetics!(saturating_minus, "saturating_minus", "subtracted");
+int_arithmetics!(saturating_star, "saturating_mul", "multiplied");
 
 
-pub fn negate(chunk: &mut Chunk, rht: (Type, (u16, u16)), op: &Token) -> Result<Type, PhoenixError> {
-    match rht.0 {
-        ty @ (Type::Int | Type::Dec) => { chunk.write_op(FBOpCode::OpNeg); Ok(ty) }
-        ty => type_error(rht.0, &[], op.pos.0, op.pos.1, format!("Type '{}' has no 'negate' function", ty)),
+/// Unsigned integers cannot be negated, an operand whose type is still open is checked by [`Infer::finish`]
+pub fn negate(infer: &mut Infer, rht: (Type, Span), op: Span) -> Result<Type, PhoenixError> {
+    match infer.shallow(rht.0) {
+        ty @ Type::Var(_) if ty.is_numeric() => { infer.negate(ty, op); Ok(ty) }
+        ty if ty.is_numeric() && !ty.is_unsigned() => Ok(ty),
+        ty => type_error(ty, &[], op, format!("Type '{}' has no 'negate' function", ty)),
     }
 }
 
-impl Module {
-    pub fn consume(&mut self, ty: TokenType) -> Result<(), PhoenixError> {
-        if self.tokens[self.i].ty != ty { 
-            let ret = Err(PhoenixError::Compile { id: CompErrID::InvalidCharacter, row: self.curr_tok().pos.0, col: self.curr_tok().pos.1, msg: format!("Expected {ty:?}, found {:?}", self.curr_tok().ty) });
-            self.i += 1; return ret
-        } self.i += 1; Ok(())
+/// A value that already has the target type needs no conversion at runtime, neither does an unsuffixed literal that can simply become it.
+/// Any other operand keeps its own type, casting a variable never changes the type of the variable.
+/// An operand whose type is still open is checked by [`Infer::finish`], once later uses have decided it.
+pub fn cast(infer: &mut Infer, from: (Type, Span), to: &ParsedType, op: Span, literal: bool) -> Result<Type, PhoenixError> {
+    if literal { if let Ok(ty) = infer.unify(from.0, to.ty) { return Ok(ty) } }
+    match infer.shallow(from.0) {
+        Type::Var(_) => infer.cast(from.0, to.clone(), op),
+        from_ty if !from_ty.castable_to(to.ty) => return type_error(from_ty, &[], op, format!("Type '{}' cannot be cast to '{}'", from_ty, to)),
+        _ => {}
     }
-
+    Ok(to.ty)
 }
-
-
-
-
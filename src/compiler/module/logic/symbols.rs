@@ -1,132 +1,77 @@
+
 use either::Either;
 
 use crate::compiler::module::Local;
 use std::sync::Arc;
-use crate::compiler::module::logic::parse_type;
-use crate::compiler::module::{TokenType, TokenType::*};
-use crate::Const;
-use crate::FBOpCode;
+use crate::compiler::module::ast::{Expr, TypeExpr, BinOp, Binding};
+use crate::compiler::module::logic::binary;
+use crate::compiler::module::fold::ConstValue;
 use crate::error::CompErrID;
 use crate::error::PhoenixError;
 use crate::compiler::module::Type;
 use crate::compiler::module::Module;
+use crate::source::Span;
 
 impl Module {
-    pub fn variable(&mut self) -> Result<Type, PhoenixError> {
-        let name = &self.curr_tok().lexeme.take().unwrap()[1..];
-        let pos = self.curr_tok().pos;
+    pub fn get_symbol(&mut self, name: &Arc<str>, pos: Span) -> Result<(Type, Binding), PhoenixError> {
+        let symbol = self.resolve_symbol(name).ok_or_else(|| PhoenixError::Compile { id: CompErrID::UnknownSymbol, span: pos,
+            msg: format!("Unknown symbol '{name}'")})?;
 
-        if [Eq, PlusEq, MinusEq, StarEq, SlashEq].contains(&self.tokens[self.i + 1].ty) {
-            self.assignment(name, pos)?; Ok(Type::Void) } else { self.get_symbol(name, pos) }
+        Ok(match symbol {
+            Either::Left((addr, loc)) => (loc.ty, Binding::Local(addr as u32)),
+            Either::Right(ty) => (ty, Binding::Global),
+        })
     }
-    
-
-    pub fn _let(&mut self) -> Result<Type, PhoenixError> {
-        self.i += 1;
-        if self.curr_tok().ty != TokenType::Identifier { return Err(PhoenixError::Compile { id: CompErrID::InvalidSymbol, row: self.curr_tok().pos.0, col: self.curr_tok().pos.1,
-        msg: format!("Variable name must be a symbol") }) }
-        let name = &self.tokens[self.i].lexeme.take().ok_or_else(|| PhoenixError::Compile { id: CompErrID::InvalidSymbol, row: self.curr_tok().pos.0, col: self.curr_tok().pos.1, 
-            msg: format!("No variable name was provided") })?[1..];
-        let pos = self.curr_tok().pos;
-
-        self.i += 1;
-        let (req_ty, req_pos): (Option<Type>, (u16, u16)) = 
-                                if self.curr_tok().ty == TokenType::Colon {
-                                    self.i += 1;
-                                    let pos = self.curr_tok().pos;
-                                    (Some(parse_type(self)?), pos)
-                                } else { (None, (0, 0)) };
-
-        self.consume(TokenType::Eq);
-        let req_pos = if req_pos == (0, 0) { self.curr_tok().pos } else { req_pos };
-
-        let ty = self.expression_parsing(0)?;
 
-        let ty = match (req_ty, ty) {
-            (None, Type::Unknown) => return Err(PhoenixError::Compile { id: CompErrID::TypeError, row: req_pos.0, col: req_pos.1,
-                msg: format!("Type cannot be inferred, must be specified") }),
-            (Some(req_ty), ty) if req_ty != ty => return Err(PhoenixError::Compile { id: CompErrID::TypeError, row: req_pos.0, col: req_pos.1,
-                msg: format!("Expected value of type '{}' as specified, type '{}' was instead provided", req_ty, ty) }),
-            (_, ty) => ty,
+    pub fn check_let(&mut self, name: &Arc<str>, req_ty: Option<&mut TypeExpr>, value: &mut Expr) -> Result<Binding, PhoenixError> {
+        let req_ty = match req_ty {
+            Some(req_ty) => { let parsed = self.resolve_type(req_ty)?; req_ty.ty = parsed.ty; Some((parsed, req_ty.span)) }
+            None => None,
         };
+        let ty = self.check_expr(value)?;
 
-        let name = self.compiler.as_mut().unwrap().lock().unwrap().strings.intern_str(name);
+        let ty = match req_ty {
+            Some((req_ty, req_pos)) => self.infer.unify(req_ty.ty, ty).map_err(|(_, ty)| PhoenixError::Compile { id: CompErrID::TypeError, span: req_pos,
+                msg: format!("Expected value of type '{}' as specified, type '{}' was instead provided", req_ty, ty) })?,
+            None => ty,
+        };
 
-        self.set_symbol(&*name, pos, ty, true);
-        Ok(Type::Void)
+        Ok(self.set_symbol(name, ty))
     }
 
-    fn assignment(&mut self, name: &str, pos: (u16, u16)) -> Result<(), PhoenixError> {
-        self.i += 1;
-        let lht = self.resolve_symbol(name).map(|either| match either { Either::Left((_, local)) => local.ty , Either::Right(ty) => ty })
-            .ok_or_else(|| PhoenixError::Compile { id: CompErrID::UnknownSymbol, row: pos.0, col: pos.1,
+    pub fn check_assign(&mut self, name: &Arc<str>, pos: Span, op: Option<(BinOp, Span)>, value: &mut Expr) -> Result<Binding, PhoenixError> {
+        if self.resolve_const(name).is_some() { return Err(PhoenixError::Compile { id: CompErrID::InvalidSymbol, span: pos,
+            msg: format!("Cannot assign to constant '{name}'") }) }
+        let (lht, binding) = self.resolve_symbol(name)
+            .map(|either| match either { Either::Left((addr, local)) => (local.ty, Binding::Local(addr as u32)), Either::Right(ty) => (ty, Binding::Global) })
+            .ok_or_else(|| PhoenixError::Compile { id: CompErrID::UnknownSymbol, span: pos,
                 msg: format!("Cannot assign to unknown symbol") })?;
 
-        let op = match self.curr_tok().ty {
-            Eq => None,
-            PlusEq | MinusEq | StarEq | SlashEq => { self.get_symbol(name, pos); Some(self.tokens[self.i].clone()) }
-            _ => unreachable!()
-        };
-        self.i += 1;
-
-        let rht_pos = self.tokens[self.i].pos;
-        let rht = self.expression_parsing(0)?;
-
+        let rht = self.check_expr(value)?;
         let expr_ty = match op {
-            Some(op) => Self::operation(self.chunk.as_mut().unwrap(), Some((lht, pos)), (rht, rht_pos), &op)?,
+            Some((op, op_span)) => binary(&mut self.infer, op, (lht, pos), (rht, value.span), op_span)?,
             None => rht,
         };
 
-        if lht != expr_ty { return Err(PhoenixError::Compile { id: CompErrID::TypeError, row: rht_pos.0, col: rht_pos.1,
-            msg: format!("Cannot assign expression of type '{expr_ty}' to symbol '{name}' of type '{lht}'") }) }
-
-        self.set_symbol(name, pos, Type::Void, false);
-        Ok(())
+        self.infer.unify(lht, expr_ty).map_err(|(lht, expr_ty)| PhoenixError::Compile { id: CompErrID::TypeError, span: value.span,
+            msg: format!("Cannot assign expression of type '{expr_ty}' to symbol '{name}' of type '{lht}'") })?;
+        Ok(binding)
     }
 
-    fn get_symbol(&mut self, name: &str, pos: (u16, u16)) -> Result<Type, PhoenixError> {
-        let symbol = self.resolve_symbol(name).ok_or_else(|| PhoenixError::Compile { id: CompErrID::UnknownSymbol, row: pos.0, col: pos.1,
-            msg: format!("Unknown symbol '{name}'")})?;
-
-        match symbol {
-            Either::Left((addr, loc)) => {
-                let ty = loc.ty; drop(loc);
-                self.chunk.as_mut().unwrap().write_op(FBOpCode::OpLocGet);
-                self.chunk.as_mut().unwrap().write(&addr.to_le_bytes()[..3]);
-                Ok(ty)
-            }
-            Either::Right(ty) => {
-                self.chunk.as_mut().unwrap().write_op(FBOpCode::OpGlobGet);
-                let name_const = self.chunk.as_mut().unwrap().add_get_const(Const::String(name.into()));
-                self.chunk.as_mut().unwrap().write(&name_const.to_le_bytes()[..3]);
-                Ok(ty)
+    /// Declares a variable, a name that is already a global is rebound to the new value instead
+    fn set_symbol(&mut self, name: &Arc<str>, ty: Type) -> Binding {
+        match self.resolve_symbol(name) {
+            Some(Either::Right(_)) => Binding::Global,
+            _ => {
+                self.locals.push(Local { name: name.clone(), depth: self.scope_depth, ty });
+                Binding::Local(self.locals.len() as u32 - 1)
             }
         }
     }
 
-
-    fn set_symbol(&mut self, name: &str, pos: (u16, u16), ty: Type, declare: bool) -> Result<(), PhoenixError> {
-        let symbol = self.resolve_symbol(name);
-
-        match symbol {
-            Some(Either::Right(ty)) => {
-                let const_addr = self.chunk.as_mut().unwrap().add_get_const(Const::String(name.into()));
-
-                self.chunk.as_mut().unwrap().write_op(FBOpCode::OpGlobSet);
-                self.chunk.as_mut().unwrap().write(&const_addr.to_le_bytes()[0..3]);
-            }
-            _ if declare => {
-                let name = self.compiler.as_mut().unwrap().lock().unwrap().strings.intern_str(name);
-                self.locals.push(Local { name, depth: self.scope_depth, ty })
-            }
-            Some(Either::Left((addr, local))) => {
-                self.chunk.as_mut().unwrap().write_op(FBOpCode::OpLocSet);
-                self.chunk.as_mut().unwrap().write(&addr.to_le_bytes()[0..3]);
-            }
-            None => return Err(PhoenixError::Compile { id: CompErrID::UnknownSymbol, row: pos.0, col: pos.1,
-                msg: format!("Cannot assign to unknown symbol '{name}'") })
-        }
-        Ok(())
+    /// Type and value of a constant, unless a variable of the same name hides it
+    pub fn resolve_const(&self, name: &str) -> Option<&(Type, ConstValue)> {
+        match self.resolve_symbol(name) { Some(Either::Left(_)) => None, _ => self.consts.get(name) }
     }
 
     fn resolve_symbol(&self, name: &str) -> Option<Either<(usize, &Local), Type>> {
@@ -0,0 +1,387 @@
+use std::{borrow::Cow, sync::Arc};
+
+use crate::{compiler::token::{Token, TokenType::{self, *}}, decimal::{Decimal, MAX_SCALE}, error::{PhoenixError, CompErrID}, source::Span};
+
+use super::{Module, MAX_NESTING, ast::{Stmt, Expr, ExprKind, Binding, BinOp, TypeExpr}, types::Type, infer::Lit};
+
+/// First pass, builds the syntax tree of the module without looking at names or types
+impl Module {
+    /// Statements of the whole module, a statement with a syntax error is reported and skipped
+    pub fn parse(&mut self) -> (Vec<Stmt>, Vec<PhoenixError>) {
+        let (mut stmts, mut errors) = (vec![], vec![]);
+
+        while self.curr_tok().ty != Eof {
+            // The scanner already reported the errors of statements it could not tokenize
+            let end = self.statement_end();
+            if self.tokens[self.i..end].iter().any(|token| token.ty == Error) { self.i = end; continue }
+
+            match self.loose_statement() {
+                Ok(stmt) => stmts.push(stmt),
+                Err(err) => { errors.push(err); self.i = self.statement_end(); self.nesting = 0; }
+            }
+        }
+        (stmts, errors)
+    }
+
+    /// Index of the token after the statement at the current token, statements end at a `;` or at the end of their logical line
+    fn statement_end(&self) -> usize {
+        let mut i = self.i;
+        while self.tok(i).ty != Eof {
+            i += 1;
+            if matches!(self.tok(i - 1).ty, SemiColon | Newline) { break; }
+        }
+        i
+    }
+
+    /// Consumes the `;` and line break that end a statement
+    fn end_statement(&mut self) { while matches!(self.curr_tok().ty, SemiColon | Newline) { self.i += 1 } }
+
+    pub fn consume(&mut self, ty: TokenType) -> Result<(), PhoenixError> {
+        if self.tok(self.i).ty != ty {
+            let ret = Err(PhoenixError::Compile { id: CompErrID::UnexpectedToken, span: self.curr_tok().span, msg: format!("Expected {ty:?}, found {:?}", self.curr_tok().ty) });
+            self.i += 1; return ret
+        } self.i += 1; Ok(())
+    }
+
+    fn loose_statement(&mut self) -> Result<Stmt, PhoenixError> {
+        if self.curr_tok().ty == Alias || (self.curr_tok().ty == Pub && self.tok(self.i + 1).ty == Alias) {
+            let alias = self.alias()?;
+            self.end_statement();
+            return Ok(alias);
+        }
+        if self.curr_tok().ty == Const {
+            let constant = self.constant()?;
+            self.end_statement();
+            return Ok(constant);
+        }
+        self.statement()
+    }
+
+    /// Expression or print statement, as found in modules and blocks
+    fn statement(&mut self) -> Result<Stmt, PhoenixError> {
+        if self.is_print() { //TODO temporary print
+            self.i += 1;
+            let expr = self.expression_parsing(0)?;
+            self.end_statement();
+            return Ok(Stmt::Print(expr));
+        }
+        let expr = self.expression_parsing(0)?;
+        self.end_statement();
+        Ok(Stmt::Expr(expr))
+    }
+
+    pub fn expression_parsing(&mut self, min_bp: u8) -> Result<Expr, PhoenixError> {
+        let nesting = self.nesting;
+        self.nest()?;
+        let expr = self.expression(min_bp);
+        self.nesting = nesting;
+        expr
+    }
+
+    /// Every nested expression and every operator applied to an expression deepens the tree, which is bounded
+    /// so that no source can overflow the stack of the passes walking it
+    fn nest(&mut self) -> Result<(), PhoenixError> {
+        if self.nesting >= MAX_NESTING {
+            return Err(PhoenixError::Compile { id: CompErrID::NestingTooDeep, span: self.curr_tok().span,
+                msg: format!("Expression is nested too deeply, at most {MAX_NESTING} levels of operators and blocks are allowed") })
+        }
+        self.nesting += 1;
+        Ok(())
+    }
+
+    fn expression(&mut self, min_bp: u8) -> Result<Expr, PhoenixError> {
+        let start = self.curr_tok().span;
+        let curr_ty = self.curr_tok().ty;
+        let mut lht = match curr_ty {
+            Let => return self._let(),
+            True | False => Expr::new(ExprKind::Bool(curr_ty == True), start),
+            Int => self.number(false, None)?,
+            Dec => self.number(true, None)?,
+            String => Expr::new(ExprKind::Str(self.sym(self.i).clone()), start),
+            InterpStart => self.interpolation()?,
+            Identifier if matches!(self.tok(self.i + 1).ty, Eq | PlusEq | MinusEq | StarEq | SlashEq) => return self.assignment(),
+            Identifier => Expr::new(ExprKind::Var { name: self.sym(self.i).clone(), binding: Binding::Unresolved }, start),
+            Plus => {
+                self.i += 1;
+                let mut expr = self.expression_parsing(9)?;
+                self.i -= 1;
+                expr.span = start.to(expr.span); expr
+            }
+            Char => Expr::new(ExprKind::Char(self.sym(self.i).chars().next().unwrap_or_default()), start),
+            LParen => {
+                self.i += 1;
+                let value = self.expression_parsing(0)?;
+                if self.curr_tok().ty != RParen {
+                    return Err(PhoenixError::Compile { id: CompErrID::UnexpectedToken, span: self.curr_tok().span,
+                        msg: format!("Expected ')' to close the parenthesis, found {:?}", self.curr_tok().ty) })
+                }
+                value
+            }
+            // Negative literals are a single constant, so `-128 as I8` fits
+            Minus if matches!(self.tok(self.i + 1).ty, Int | Dec) => {
+                self.i += 1;
+                let dec = self.curr_tok().ty == Dec;
+                self.number(dec, Some(start))?
+            }
+            op @ Minus => {
+                let ((), r_bp) = prefix_bp(op).ok_or_else(|| PhoenixError::Compile { id: CompErrID::UnexpectedToken, span: start,
+                    msg: format!("{op:?} is not a prefix operator") })?;
+                self.i += 1;
+                let rhs = self.expression_parsing(r_bp)?;
+                self.i -= 1;
+                let span = start.to(rhs.span);
+                Expr::new(ExprKind::Neg { op_span: start, expr: Box::new(rhs) }, span)
+            }
+            Alias => return Err(PhoenixError::Compile { id: CompErrID::InvalidSymbol, span: start,
+                msg: format!("Aliases can only be declared at module level") }),
+            Const => return Err(PhoenixError::Compile { id: CompErrID::InvalidSymbol, span: start,
+                msg: format!("Constants can only be declared at module level") }),
+            op @ (LBrace | IndentUp) => return self.block(op == LBrace),
+            ty => return Err(PhoenixError::Compile { id: CompErrID::UnexpectedToken, span: start,
+                msg: format!("Unexpected {ty:?} at the start of an expression") })
+        };
+        self.i += 1;
+        lht.span = start.to(self.tok(self.i - 1).span);
+
+        loop {
+            let op_i = self.i;
+            let op = match self.curr_tok().ty {
+                SemiColon | Newline | RParen | RBrace | InterpMid | InterpEnd | Eof  => break,
+                op @ (Plus | Minus | Star | Slash | As | WrapPlus | WrapMinus | WrapStar | SatPlus | SatMinus | SatStar) => *self.tok(op_i),
+                op => return Err(PhoenixError::Compile { id: CompErrID::UnexpectedToken, span: self.curr_tok().span,
+                    msg: format!("Unexpected {op:?} after an expression") }),
+            };
+
+            if let Some((l_bp, ())) = postfix_bp(op.ty) { // Postfix
+                if l_bp < min_bp { break; }
+                self.nest()?;
+                self.i += 1;
+
+                lht = match op.ty {
+                    As => {
+                        let to = self.parse_type()?;
+                        let span = lht.span.to(to.span);
+                        Expr::new(ExprKind::Cast { op_span: op.span, expr: Box::new(lht), to }, span)
+                    }
+                    _ => lht,
+                };
+                continue;
+            }
+
+            if let Some((l_bp, r_bp)) = infix_bp(op.ty) { // Infix
+                if l_bp < min_bp { break; }
+                self.nest()?;
+                self.i += 1;
+
+                let rht = self.expression_parsing(r_bp)?;
+                let span = lht.span.to(rht.span);
+                let Some(bin_op) = bin_op(op.ty) else {
+                    return Err(PhoenixError::Compile { id: CompErrID::UnexpectedToken, span: op.span, msg: format!("{:?} is not an operator on values", op.ty) })
+                };
+                lht = Expr::new(ExprKind::Binary { op: bin_op, op_span: op.span, lhs: Box::new(lht), rhs: Box::new(rht) }, span);
+                continue;
+            }
+
+            break
+        }
+
+        Ok(lht)
+    }
+
+    /// Value of an integer or decimal literal, `neg` is the span of the `-` written before it
+    fn number(&mut self, dec: bool, neg: Option<Span>) -> Result<Expr, PhoenixError> {
+        let pos = self.curr_tok().span;
+        let source = self.source.clone();
+        let lexeme = source.slice(pos);
+        let lexeme = if neg.is_some() { Cow::Owned(format!("-{lexeme}")) } else { Cow::Borrowed(lexeme) };
+        let (sign, body) = match lexeme.strip_prefix('-') { Some(body) => ("-", body), None => ("", &*lexeme) };
+        let radix = match body.get(..2) { Some("0x") => 16, Some("0o") => 8, Some("0b") => 2, _ => 10 };
+        let body = if radix == 10 { body } else { &body[2..] };
+        // Hexadecimal digits include 'f' and exponents 'e', so only these letters can start a suffix
+        let split = body.find(|c: char| c == 'i' || c == 'u' || (c == 'f' && radix != 16)).unwrap_or(body.len());
+        let (digits, suffix) = body.split_at(split);
+        let digits = format!("{sign}{}", digits.replace('_', ""));
+        let err = |id, msg| PhoenixError::Compile { id, span: pos, msg };
+
+        let suffix = match suffix {
+            "" => None,
+            suffix => Some(Type::from_suffix(suffix).ok_or_else(|| err(CompErrID::InvalidNumber, format!("Invalid suffix '{suffix}' for numeric literal")))?),
+        };
+
+        // Binary floats are parsed directly, their exponent may be far outside of what Dec can hold
+        let value = if let Some(ty @ (Type::F32 | Type::F64)) = suffix.filter(|_| radix == 10) {
+            Lit::Float(digits.parse::<f64>().ok().filter(|f| f.is_finite() && (ty == Type::F64 || (*f as f32).is_finite()))
+                .ok_or_else(|| err(CompErrID::InvalidNumber, format!("Literal '{lexeme}' does not fit in type '{ty}'")))?)
+        } else if dec {
+            Lit::Dec(digits.parse::<Decimal>().map_err(|_| err(CompErrID::InvalidNumber, format!("Decimal literal '{lexeme}' has more than {MAX_SCALE} decimals or too many digits")))?)
+        } else { Lit::Int(i128::from_str_radix(&digits, radix).map_err(|_| err(CompErrID::InvalidNumber, format!("Integer literal '{lexeme}' is too large")))?) };
+
+        Ok(Expr::new(ExprKind::Number { value, suffix, lexeme: lexeme.into() }, neg.map_or(pos, |neg| neg.to(pos))))
+    }
+
+    /// String parts and expressions of an interpolated string, empty parts are left out
+    fn interpolation(&mut self) -> Result<Expr, PhoenixError> {
+        let start = self.curr_tok().span;
+        let mut parts = vec![];
+        loop {
+            let (ty, span, str) = (self.curr_tok().ty, self.curr_tok().span, self.sym(self.i).clone());
+            if !str.is_empty() { parts.push(Expr::new(ExprKind::Str(str), span)) }
+            if ty == InterpEnd { break }
+
+            self.i += 1;
+            parts.push(self.expression_parsing(0)?);
+            if !matches!(self.curr_tok().ty, InterpMid | InterpEnd) {
+                return Err(PhoenixError::Compile { id: CompErrID::UnexpectedToken, span: self.curr_tok().span,
+                    msg: format!("Expected '}}' to close the interpolation, found {:?}", self.curr_tok().ty) })
+            }
+        }
+        Ok(Expr::new(ExprKind::Interp(parts), start.to(self.curr_tok().span)))
+    }
+
+    /// Block opened by `{` on a single line, or by an indentation
+    fn block(&mut self, braces: bool) -> Result<Expr, PhoenixError> {
+        let start = self.curr_tok().span;
+        self.i += 1;
+
+        let mut stmts = vec![];
+        while ![Eof, RBrace, IndentDown].contains(&self.curr_tok().ty) { stmts.push(self.statement()?) }
+
+        let end = self.curr_tok().span;
+        if braces && self.line(end) != self.line(start) {
+            return Err(PhoenixError::Compile { id: CompErrID::InvalidCharacter, span: end,
+                msg: format!("Braces can be used only for single-line blocks") })
+        }
+        if !braces && self.curr_tok().ty == RBrace {
+            return Err(PhoenixError::Compile { id: CompErrID::InvalidCharacter, span: end,
+                msg: format!("Cannot terminate multiline block with '}}'") })
+        }
+        // The `;` of the last statement is before the line breaks ending the block
+        let mut last = self.i - 1;
+        while self.tok(last).ty == Newline { last -= 1 }
+        let void = !stmts.is_empty() && self.tok(last).ty == SemiColon;
+        self.i += 1;
+        Ok(Expr::new(ExprKind::Block { stmts, locals: 0, void }, start.to(end)))
+    }
+
+    pub fn _let(&mut self) -> Result<Expr, PhoenixError> {
+        let start = self.curr_tok().span;
+        self.i += 1;
+        if self.curr_tok().ty != TokenType::Identifier { return Err(PhoenixError::Compile { id: CompErrID::InvalidSymbol, span: self.curr_tok().span,
+            msg: format!("Variable name must be a symbol") }) }
+        let name = self.sym(self.i).clone();
+        let name_span = self.curr_tok().span;
+
+        self.i += 1;
+        let ty = if self.curr_tok().ty == TokenType::Colon { self.i += 1; Some(self.parse_type()?) } else { None };
+
+        self.consume(TokenType::Eq)?;
+        let value = self.expression_parsing(0)?;
+        let span = start.to(value.span);
+        Ok(Expr::new(ExprKind::Let { name, name_span, ty, value: Box::new(value), binding: Binding::Unresolved }, span))
+    }
+
+    fn assignment(&mut self) -> Result<Expr, PhoenixError> {
+        let (name, name_span) = (self.sym(self.i).clone(), self.curr_tok().span);
+        self.i += 1;
+        let op_span = self.curr_tok().span;
+        let op = match self.curr_tok().ty { PlusEq => Some(BinOp::Add), MinusEq => Some(BinOp::Sub), StarEq => Some(BinOp::Mul), SlashEq => Some(BinOp::Div), _ => None };
+        self.i += 1;
+
+        let value = self.expression_parsing(0)?;
+        let span = name_span.to(value.span);
+        Ok(Expr::new(ExprKind::Assign { name, name_span, op, op_span, value: Box::new(value), binding: Binding::Unresolved }, span))
+    }
+
+    /// `alias Name = Type`, `pub alias` is refused until modules can import from each other
+    fn alias(&mut self) -> Result<Stmt, PhoenixError> {
+        if self.curr_tok().ty == TokenType::Pub {
+            return Err(PhoenixError::Compile { id: CompErrID::UnexpectedToken, span: self.curr_tok().span,
+                msg: format!("Aliases cannot be 'pub' yet, modules cannot import from each other") })
+        }
+        self.consume(TokenType::Alias)?;
+
+        let span = self.curr_tok().span;
+        let name = match *self.tok(self.i) {
+            Token { ty: TokenType::Identifier, can_be_type: true, .. } => self.sym(self.i).clone(),
+            _ => return Err(PhoenixError::Compile { id: CompErrID::InvalidSymbol, span,
+                msg: format!("Alias name must be a valid type name") }),
+        };
+        self.i += 1;
+        self.consume(TokenType::Eq)?;
+        let ty = self.parse_type()?;
+        Ok(Stmt::Alias { name, span, ty })
+    }
+
+    /// `const NAME: Type = value`, the type is required
+    fn constant(&mut self) -> Result<Stmt, PhoenixError> {
+        self.consume(TokenType::Const)?;
+        let span = self.curr_tok().span;
+        if self.curr_tok().ty != TokenType::Identifier { return Err(PhoenixError::Compile { id: CompErrID::InvalidSymbol, span,
+            msg: format!("Constant name must be a symbol") }) }
+        let name = self.sym(self.i).clone();
+        self.i += 1;
+
+        if self.curr_tok().ty != TokenType::Colon { return Err(PhoenixError::Compile { id: CompErrID::TypeError, span: self.curr_tok().span,
+            msg: format!("Constant '{name}' must specify its type") }) }
+        self.i += 1;
+        let ty = self.parse_type()?;
+        self.consume(TokenType::Eq)?;
+        let value = self.expression_parsing(0)?;
+        Ok(Stmt::Const { name, span, ty, value })
+    }
+
+    /// Name of a type, `()` being the void type
+    fn parse_type(&mut self) -> Result<TypeExpr, PhoenixError> {
+        let (span, ty) = (self.curr_tok().span, self.curr_tok().ty);
+        match ty {
+            TokenType::LParen if self.tok(self.i + 1).ty == TokenType::RParen => {
+                self.i += 2;
+                Ok(TypeExpr { name: Arc::from("()"), span: span.to(self.tok(self.i - 1).span), ty: Type::Void })
+            }
+            TokenType::Identifier => {
+                self.i += 1;
+                Ok(TypeExpr { name: self.sym(self.i - 1).clone(), span, ty: Type::Void })
+            }
+            _ => Err(PhoenixError::Compile { id: CompErrID::TypeError, span,
+                msg: format!("Invalid or non-existent type") })
+        }
+    }
+}
+
+fn bin_op(op: TokenType) -> Option<BinOp> {
+    Some(match op {
+        Plus => BinOp::Add, Minus => BinOp::Sub, Star => BinOp::Mul, Slash => BinOp::Div,
+        WrapPlus => BinOp::WrapAdd, WrapMinus => BinOp::WrapSub, WrapStar => BinOp::WrapMul,
+        SatPlus => BinOp::SatAdd, SatMinus => BinOp::SatSub, SatStar => BinOp::SatMul,
+        _ => return None,
+    })
+}
+
+// bp stands for binding power
+
+fn prefix_bp(op: TokenType) -> Option<((), u8)> {
+    let res = match op {
+        Plus | Minus => ((), 9),
+        _ => return None,
+    };
+    Some(res)
+}
+fn postfix_bp(op: TokenType) -> Option<(u8, ())> {
+    let res = match op {
+//        '[' => (11, ()),
+        As => (11, ()),
+        _ => return None,
+    };
+    Some(res)
+}
+fn infix_bp(op: TokenType) -> Option<(u8, u8)> {
+    let res = match op {
+        Eq | PlusEq | MinusEq | StarEq | SlashEq => (2, 1),
+        Identifier => (4, 3),
+        Plus | Minus | WrapPlus | WrapMinus | SatPlus | SatMinus => (5, 6),
+        Star | Slash | WrapStar | SatStar => (7, 8),
+        Dot => (14, 13),
+        _ => return None,
+    };
+    Some(res)
+}
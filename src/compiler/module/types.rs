@@ -1,19 +1,29 @@
-use std::{fmt::{Display, Debug}, any::Any, str::FromStr};
+use std::{fmt::{Display, Debug}, any::Any, str::FromStr, sync::Arc};
 
-use crate::{compiler::token::TokenType, error::{PhoenixError, CompErrID}};
+use crate::{decimal::Decimal, compiler::chunk::Const, error::{PhoenixError, CompErrID}, flamebytecode::CastTy};
 
-use super::Module;
+use super::{Module, ast::TypeExpr};
+use super::infer::{VarId, VarKind, Lit};
 
 
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Type {
-    Void, Bool, Dec, Int, Str, Char, Unknown,
+    Void, Bool, Dec, Int, Str, Char,
+    I8, I16, I32, I64, U8, U16, U32, U64, F32, F64,
+    /// Not known yet, resolved through the module's [`Infer`](super::infer::Infer) table
+    Var(VarId),
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct ParseTypeError;
 
+/// A type as written in the source, `alias` holds the name it was spelled with when it is an alias
+#[derive(Clone, Debug)]
+pub struct ParsedType { pub ty: Type, pub alias: Option<Arc<str>> }
+
+pub struct Alias { pub ty: Type }
+
 impl FromStr for Type {
     type Err = ParseTypeError;
 
@@ -21,6 +31,9 @@ impl FromStr for Type {
         match s {
             "Void" | "()" => Ok(Self::Void), "Bool" => Ok(Self::Bool), "Dec" => Ok(Self::Dec), 
             "Int" => Ok(Self::Int), "Str" => Ok(Self::Str), "Char" => Ok(Self::Char),
+            "I8" => Ok(Self::I8), "I16" => Ok(Self::I16), "I32" => Ok(Self::I32), "I64" => Ok(Self::I64),
+            "U8" => Ok(Self::U8), "U16" => Ok(Self::U16), "U32" => Ok(Self::U32), "U64" => Ok(Self::U64),
+            "F32" => Ok(Self::F32), "F64" => Ok(Self::F64),
             _ => Err(ParseTypeError)
         }
     }
@@ -32,12 +45,119 @@ impl Display for Type {
             Type::Void => write!(f, "Void"), Type::Bool => write!(f, "Bool"),
             Type::Dec => write!(f, "Dec"), Type::Int => write!(f, "Int"),
             Type::Str => write!(f, "Str"), Type::Char => write!(f, "Char"),
-            Type::Unknown => unreachable!("Tried to print Unknown"),
+            Type::I8 => write!(f, "I8"), Type::I16 => write!(f, "I16"), Type::I32 => write!(f, "I32"), Type::I64 => write!(f, "I64"),
+            Type::U8 => write!(f, "U8"), Type::U16 => write!(f, "U16"), Type::U32 => write!(f, "U32"), Type::U64 => write!(f, "U64"),
+            Type::F32 => write!(f, "F32"), Type::F64 => write!(f, "F64"),
+            Type::Var(VarId { kind: VarKind::Any, .. }) => write!(f, "{{unknown}}"),
+            Type::Var(VarId { kind: VarKind::IntLit | VarKind::Integral, .. }) => write!(f, "{{integer}}"),
+            Type::Var(VarId { kind: VarKind::DecLit, .. }) => write!(f, "{{decimal}}"),
+        }
+    }
+}
+
+impl Display for ParsedType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.alias {
+            Some(alias) => write!(f, "{alias} (alias of {})", self.ty),
+            None => write!(f, "{}", self.ty),
         }
     }
 }
 
 impl Type {
+    pub fn is_int(&self) -> bool {
+        matches!(self, Type::Int | Type::I8 | Type::I16 | Type::I32 | Type::I64 | Type::U8 | Type::U16 | Type::U32 | Type::U64)
+    }
+    pub fn is_unsigned(&self) -> bool { matches!(self, Type::U8 | Type::U16 | Type::U32 | Type::U64) }
+    /// Types a decimal literal can be, the exact Dec and the binary floats
+    pub fn is_fractional(&self) -> bool { matches!(self, Type::Dec | Type::F32 | Type::F64) }
+    pub fn is_numeric(&self) -> bool {
+        self.is_int() || self.is_fractional() || matches!(self, Type::Var(VarId { kind: VarKind::IntLit | VarKind::DecLit | VarKind::Integral, .. }))
+    }
+
+    /// Smallest and largest value of an integer type
+    pub fn int_bounds(&self) -> Option<(i128, i128)> {
+        Some(match self {
+            Type::Int | Type::I64 => (i64::MIN as i128, i64::MAX as i128),
+            Type::I8 => (i8::MIN as i128, i8::MAX as i128), Type::I16 => (i16::MIN as i128, i16::MAX as i128), Type::I32 => (i32::MIN as i128, i32::MAX as i128),
+            Type::U8 => (0, u8::MAX as i128), Type::U16 => (0, u16::MAX as i128), Type::U32 => (0, u32::MAX as i128), Type::U64 => (0, u64::MAX as i128),
+            _ => return None,
+        })
+    }
+
+    /// Type selected by a numeric literal suffix such as the `u8` in `255u8`
+    pub fn from_suffix(suffix: &str) -> Option<Type> {
+        match suffix {
+            "i8" => Some(Type::I8), "i16" => Some(Type::I16), "i32" => Some(Type::I32), "i64" => Some(Type::I64),
+            "u8" => Some(Type::U8), "u16" => Some(Type::U16), "u32" => Some(Type::U32), "u64" => Some(Type::U64),
+            "f32" => Some(Type::F32), "f64" => Some(Type::F64),
+            _ => None,
+        }
+    }
+
+    /// Constant for an integer literal of this type, `None` if it does not fit, or is not exactly representable as a float
+    pub fn int_const(&self, v: i128) -> Option<Const> {
+        // Floats of 2^127 and more saturate when converted back, so they never round-trip
+        let exact = |f: f64| (f.abs() < i128::MAX as f64 && f as i128 == v).then_some(f);
+        Some(match self {
+            Type::Int => Const::Int(v.try_into().ok()?), Type::I64 => Const::I64(v.try_into().ok()?),
+            Type::I8 => Const::I8(v.try_into().ok()?), Type::I16 => Const::I16(v.try_into().ok()?), Type::I32 => Const::I32(v.try_into().ok()?),
+            Type::U8 => Const::U8(v.try_into().ok()?), Type::U16 => Const::U16(v.try_into().ok()?),
+            Type::U32 => Const::U32(v.try_into().ok()?), Type::U64 => Const::U64(v.try_into().ok()?),
+            Type::Dec => Const::Dec(Decimal::from_int(v)),
+            Type::F32 => Const::F32((exact(v as f32 as f64)? as f32).to_bits()), Type::F64 => Const::F64(exact(v as f64)?.to_bits()),
+            _ => return None,
+        })
+    }
+
+    /// Constant for a decimal literal of this type
+    pub fn dec_const(&self, v: Decimal) -> Option<Const> {
+        match self {
+            Type::Dec => Some(Const::Dec(v)),
+            Type::F32 if (v.to_f64() as f32).is_finite() => Some(Const::F32((v.to_f64() as f32).to_bits())),
+            Type::F64 => Some(Const::F64(v.to_f64().to_bits())),
+            _ => None,
+        }
+    }
+
+    /// Constant for a literal of this type, `None` if it does not fit
+    pub fn lit_const(&self, lit: Lit) -> Option<Const> {
+        match lit {
+            Lit::Int(v) => self.int_const(v),
+            Lit::Dec(v) => self.dec_const(v),
+            Lit::Float(v) => match self {
+                Type::F32 => Some(Const::F32((v as f32).to_bits())),
+                Type::F64 => Some(Const::F64(v.to_bits())),
+                _ => None,
+            }
+        }
+    }
+
+    /// Whether `as` can convert a value of this type to `to`
+    pub fn castable_to(&self, to: Type) -> bool {
+        match (*self, to) {
+            (from, to) if from == to => true,
+            (from, to) if from.is_numeric() && to.is_numeric() => true,
+            (from, Type::Char) if from.is_int() => true,
+            (Type::Char | Type::Bool, to) if to.is_int() => true,
+            (from, Type::Str) if from.is_numeric() || matches!(from, Type::Char | Type::Bool) => true,
+            (Type::Str, to) if to.is_numeric() || to == Type::Char => true,
+            _ => false,
+        }
+    }
+
+    /// Target operand of `OpCast`, `None` for types that cannot be cast to
+    pub fn cast_ty(&self) -> Option<CastTy> {
+        match self {
+            Type::Int => Some(CastTy::Int), Type::Dec => Some(CastTy::Dec),
+            Type::Char => Some(CastTy::Char), Type::Str => Some(CastTy::Str),
+            Type::I8 => Some(CastTy::I8), Type::I16 => Some(CastTy::I16), Type::I32 => Some(CastTy::I32), Type::I64 => Some(CastTy::I64),
+            Type::U8 => Some(CastTy::U8), Type::U16 => Some(CastTy::U16), Type::U32 => Some(CastTy::U32), Type::U64 => Some(CastTy::U64),
+            Type::F32 => Some(CastTy::F32), Type::F64 => Some(CastTy::F64),
+            _ => None,
+        }
+    }
+
     pub fn bytes() -> Vec<u8> {
         // TODO
         //      todo string encoding implementation, for now only utf-8
@@ -45,21 +165,14 @@ impl Type {
     }
 }
 
-pub fn parse_type(module: &mut Module) -> Result<Type, PhoenixError> {
-    let pos = module.curr_tok().pos;
-    let t = &module.tokens[module.i];
-    match t.ty {
-        TokenType::LParen if module.tokens[module.i].ty == TokenType::RParen => {
-            module.i += 1;
-            Ok(Type::Void)
-        }
-        TokenType::Identifier => {
-            let str = module.curr_tok().lexeme.take().unwrap();
-            Type::from_str(&*str).map_err(|_|
-                PhoenixError::Compile { id: CompErrID::TypeError, row: pos.0, col: pos.1,
-                msg: format!("Type '{}' is non-existent", str) })
+impl Module {
+    /// Type named by a type expression, either a builtin or an alias of this module
+    pub fn resolve_type(&self, ty: &TypeExpr) -> Result<ParsedType, PhoenixError> {
+        if let Ok(builtin) = Type::from_str(&ty.name) { return Ok(ParsedType { ty: builtin, alias: None }) }
+        match self.aliases.get_key_value(&*ty.name) {
+            Some((alias, Alias { ty, .. })) => Ok(ParsedType { ty: *ty, alias: Some(alias.clone()) }),
+            None => Err(PhoenixError::Compile { id: CompErrID::TypeError, span: ty.span,
+                msg: format!("Type '{}' is non-existent", ty.name) }),
         }
-        _ => Err(PhoenixError::Compile { id: CompErrID::TypeError, row: pos.0, col: pos.1,
-            msg: format!("Invalid or non-existent type") })
     }
 }
@@ -0,0 +1,48 @@
+use crate::flamebytecode::FBOpCode::{self, *};
+
+use super::chunk::Chunk;
+
+/// Rewrites the emitted code of a release build.
+/// Values pushed only to be popped right away are never pushed and runs of pops become a single `OpPopN`.
+pub fn optimize(chunk: &mut Chunk) {
+    let sizes = FBOpCode::size();
+    let mut out: Vec<u8> = Vec::with_capacity(chunk.code.len());
+    // Offsets of the instructions already in `out`
+    let mut starts: Vec<usize> = vec![];
+
+    let mut i = 0;
+    while i < chunk.code.len() {
+        let size = sizes[chunk.code[i] as usize] as usize;
+        let instr = &chunk.code[i..i + size];
+        i += size;
+
+        let mut pops = match FBOpCode::from(instr[0]) {
+            OpPop => 1,
+            OpPopN => instr[1] as usize,
+            _ => { starts.push(out.len()); out.extend_from_slice(instr); continue }
+        };
+
+        // A push without side effects followed by a pop cancels out
+        while pops > 0 && starts.last().is_some_and(|&at| is_pure_push(FBOpCode::from(out[at]))) {
+            out.truncate(starts.pop().unwrap());
+            pops -= 1;
+        }
+        if pops == 0 { continue }
+
+        // Pops following each other are counted by a single instruction
+        if let Some(&at) = starts.last() {
+            let prev = match FBOpCode::from(out[at]) { OpPop => 1, OpPopN => out[at + 1] as usize, _ => 0 };
+            if prev > 0 { out.truncate(at); starts.pop(); pops += prev }
+        }
+        while pops > 0 {
+            let n = pops.min(u8::MAX as usize);
+            starts.push(out.len());
+            if n == 1 { out.push(OpPop as u8) } else { out.extend_from_slice(&[OpPopN as u8, n as u8]) }
+            pops -= n;
+        }
+    }
+    chunk.code = out;
+}
+
+/// Instructions that only push a value, removing them along with the pop of that value changes nothing
+fn is_pure_push(op: FBOpCode) -> bool { matches!(op, OpConstant | OpTrue | OpFalse | OpLocGet | OpGlobGet) }
@@ -4,218 +4,517 @@ use std::str::Chars;
 
 use ahash::AHasher;
 
-use crate::{utils::{StringExt, OwnedChars}, error::{PhoenixError, CompErrID}, IDENTIFIER_MAX_LENGTH};
-use crate::compiler::token::{Token, TokenType::{*, self}};
+use unicode_normalization::UnicodeNormalization;
+use unicode_xid::UnicodeXID;
+
+use crate::{utils::{StringExt, OwnedChars}, error::{PhoenixError, CompErrID}, source::{FileId, Span}};
+use crate::compiler::token::{Token, TokenType::{*, self}, Symbols, Trivia, TriviaKind, TriviaMap};
 
 type AHashMap<K, V> = HashMap<K, V, BuildHasherDefault<AHasher>>;
 
+/// How a project indents its blocks, set by `indent-style` and `indent-width` in the `syntax` table of `Feather.toml`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Indent { Spaces(u8), Tabs }
+
 pub struct Scanner {
     src: OwnedChars, 
     peek: Option<char>, peek_more: Option<char>,
-    pub row: u16, pub col: u16, indent: u16,
+    file: FileId,
+    /// Byte offset of `peek`, of the last consumed character and of the token being scanned
+    offset: u32, prev: u32, pub start: u32,
+    /// Offset of the first character of `src` in the file, not 0 for interpolated expressions
+    base: u32,
+    symbols: Symbols,
+    /// Comments and whitespace in source order, only kept by [`Scanner::scan_lossless`]
+    trivia: Option<Vec<Trivia>>,
+    /// Levels of the enclosing indented blocks, the innermost last
+    indents: Vec<u16>,
+    indent_style: Indent,
+    /// Longest identifier accepted, in characters, unlimited when `None`
+    max_identifier_length: Option<usize>,
+    /// Lexical errors found so far, scanning resumes after each of them
+    errors: Vec<PhoenixError>,
 }
 
 
 impl Scanner {
-    pub fn new(src: String) -> Self { let mut iter = src.into_chars(); Self { peek: iter.next(), peek_more: iter.next(), src: iter, row: 1, col: 0, indent: 0, }}
+    pub fn new(src: String, file: FileId) -> Self { 
+        let mut iter = src.into_chars();
+        Self { peek: iter.next(), peek_more: iter.next(), src: iter, file, offset: 0, prev: 0, start: 0, base: 0, symbols: Symbols::default(), trivia: None, indents: vec![0], indent_style: Indent::Spaces(4), max_identifier_length: None, errors: vec![] }
+    }
+    pub fn with_max_identifier_length(mut self, max: Option<usize>) -> Self { self.max_identifier_length = max; self }
+    pub fn with_indent(mut self, indent: Indent) -> Self { self.indent_style = indent; self }
     fn next(&mut self) -> Option<char> {
         let ret = self.peek; self.peek = self.peek_more; self.peek_more = self.src.next(); 
-        if ret.is_some_and(|x| x == '\n') { self.row += 1; self.col = 1 } else { self.col += 1 } 
+        if let Some(c) = ret { self.prev = self.offset; self.offset += c.len_utf8() as u32 }
         ret 
     }
+    pub fn span(&self) -> Span { Span::new(self.file, self.start, self.offset) }
+    /// Source text from `start` up to the next character
+    fn text(&self, start: u32) -> &str { &self.src.as_str()[(start - self.base) as usize..(self.offset - self.base) as usize] }
+    fn last_span(&self) -> Span { Span::new(self.file, self.prev, self.offset) }
+    fn peek_span(&self) -> Span { Span::new(self.file, self.offset, self.offset + self.peek.map_or(0, |c| c.len_utf8() as u32)) }
     fn make_double(&mut self, expected: char, single: TokenType, double: TokenType) -> Token {
-        if (self.peek.is_some_and(|x| x == expected)) { self.next(); Token::make_pos(double, None, (self.row, self.col - 1)) }
-        else { Token::make(&self, single, None) }
+        if (self.peek.is_some_and(|x| x == expected)) { self.next(); Token::make(&self, double) }
+        else { Token::make(&self, single) }
+    }
+    fn make_choice(&mut self, single: TokenType, doubles: &[(char, TokenType)]) -> Token {
+        match doubles.iter().find(|(expected, _)| self.peek == Some(*expected)) {
+            Some((expected, double)) => self.make_double(*expected, single, *double),
+            None => Token::make(&self, single),
+        }
     }
     fn next_err(&mut self, err_id: CompErrID, err_msg: String) -> Result<char, PhoenixError> { 
         match self.next() {
-            None | Some('\0') => Err(PhoenixError::Compile { id: err_id, row: self.row, col: self.col, msg: err_msg }),
+            None | Some('\0') => Err(PhoenixError::Compile { id: err_id, span: self.span(), msg: err_msg }),
             Some(c) => Ok(c),
         }
     }
     fn expect_char(&mut self, expected: char) -> Result<char, PhoenixError> {
         match self.next() {
             Some(c) if c == expected => Ok(expected),
-            Some(c) => Err(PhoenixError::Compile { id: CompErrID::InvalidCharacter, row: self.row, col: self.col, msg: format!("Expected '{expected}', found '{c}'") }),
-            None => Err(PhoenixError::Compile { id: CompErrID::InvalidCharacter, row: self.row, col: self.col, msg: format!("Expected '{expected}'") }),
+            Some(c) => Err(PhoenixError::Compile { id: CompErrID::InvalidCharacter, span: self.last_span(), msg: format!("Expected '{expected}', found '{c}'") }),
+            None => Err(PhoenixError::Compile { id: CompErrID::InvalidCharacter, span: self.last_span(), msg: format!("Expected '{expected}'") }),
         }
     }
 
-    pub fn scan(mut self) -> Result<Vec<Token>, PhoenixError> {
+    /// Tokens of the whole source along with every lexical error, the source that caused an error becomes an `Error` token
+    pub fn scan(self) -> (Vec<Token>, Symbols, Vec<PhoenixError>) { let (tokens, symbols, _, errors) = self.run(); (tokens, symbols, errors) }
+
+    /// Like [`Scanner::scan`], but also keeps comments, whitespace and line breaks so the source can be rebuilt exactly
+    pub fn scan_lossless(mut self) -> (Vec<Token>, Symbols, TriviaMap, Vec<PhoenixError>) {
+        self.trivia = Some(vec![]);
+        let (tokens, symbols, pieces, errors) = self.run();
+        let trivia = TriviaMap::new(&tokens, pieces);
+        (tokens, symbols, trivia, errors)
+    }
+
+    /// Records `err` and skips to the next whitespace or delimiter, the skipped source becomes an `Error` token
+    fn recover(&mut self, res: &mut Vec<Token>, err: PhoenixError) {
+        self.errors.push(err);
+        while self.peek.is_some_and(|c| !c.is_whitespace() && !matches!(c, '\0' | '(' | ')' | '[' | ']' | '{' | '}' | ',' | ';')) { self.next(); }
+        res.push(Token::make(self, Error));
+    }
+
+    /// Records the trivia scanned since `start`, only in lossless mode
+    fn trivia(&mut self, kind: TriviaKind, start: u32) {
+        let span = Span::new(self.file, start, self.offset);
+        if let Some(trivia) = &mut self.trivia { if start != self.offset { trivia.push(Trivia { kind, span }) } }
+    }
+
+    fn run(mut self) -> (Vec<Token>, Symbols, Vec<Trivia>, Vec<PhoenixError>) {
         let keywords = AHashMap::from_iter(
-            [ ("and", And), ("alias", Alias), ("as", As), ("else", Else), ("false", False), ("fn", Fn), ("if", If),
+            [ ("and", And), ("alias", Alias), ("as", As), ("const", Const), ("else", Else), ("false", False), ("fn", Fn), ("if", If),
             ("infix", Infix), ("let", Let), ("loop", Loop), ("not", Not), ("macro", Macro), ("mod", Mod), ("mut", Mut),
             ("or", Or), ("pub", Pub), ("return", Return), ("self", Selff), 
             ("struct", Struct), ("super", Super), ("trait", Trait), ("true", True), ("while", While), ("xor", Xor) ]);
         let mut res = vec![];
         let mut c_ = None; let mut c = '0';
+        // Open `(`, `[` and `{`, line breaks inside them do not end the line
+        let mut brackets = 0u32;
 
         'scanner: loop {
+            self.start = self.offset;
             macro_rules! next_break {
                 () => {
                     {
                         c_ = self.next();
-                        if (!c_.is_some_and(|x| x != '\0')) { res.push(Token::make(&self, Eof, None)); break 'scanner }
+                        if (!c_.is_some_and(|x| x != '\0')) { res.push(Token::make(&self, Eof)); break 'scanner }
                         c = c_.unwrap();
                     }
                 };
             }
 
+            macro_rules! recover { ($result:expr) => { if let Err(err) = $result { self.recover(&mut res, err) } }; }
+
             next_break!();
 
             match c {
                 '\n' => {
-                    let mut spaces = 0;
-                    while self.peek.is_some_and(|x| x == ' ') { spaces += 1; next_break!() }
-                    let new_indent = spaces / 4; 
-                    let diff = new_indent as i32 - self.indent as i32;
-                    let tok = [IndentDown, IndentUp]; let tok_i = diff > 0;
-                    (0..diff.abs()).for_each(|_| {
-                        if res.last().is_some_and(|x| x.ty == tok[!tok_i as usize]) { res.pop(); }
-                        else { res.push(Token::make(&self, tok[tok_i as usize], None)) }
-                    });
-                    self.indent = new_indent;
+                    // Only the first line break after code ends a logical line, the others are trivia
+                    let continued = brackets > 0 || res.last().is_some_and(|x| x.ty.continues_line());
+                    if continued || res.last().map_or(true, |x| x.ty == Newline) { self.trivia(TriviaKind::Newline, self.start) }
+                    else { res.push(Token::make(&self, Newline)) }
+
+                    let indent_start = self.offset;
+                    let (mut spaces, mut tabs) = (0, 0);
+                    while let Some(c) = self.peek.filter(|c| *c == ' ' || *c == '\t') { if c == ' ' { spaces += 1 } else { tabs += 1 }; self.next(); }
+                    self.trivia(TriviaKind::Whitespace, indent_start);
+
+                    // The indentation of continuation lines is free
+                    if continued { continue }
+
+                    // Blank and comment only lines keep the current level
+                    if self.comment_only_line() { continue }
+                    let old_indent = self.indent();
+                    // A bad line continues at the innermost level it can belong to
+                    let new_indent = match self.indent_level(spaces, tabs, Span::new(self.file, indent_start, self.offset)) {
+                        Ok(level) => level,
+                        Err(err) => { self.errors.push(err); self.indent() }
+                    };
+
+                    // Indentation tokens are empty, placed where the code of the new line starts
+                    self.start = self.offset;
+                    let diff = new_indent as i32 - old_indent as i32;
+                    let tok = if diff > 0 { IndentUp } else { IndentDown };
+                    (0..diff.abs()).for_each(|_| res.push(Token::make(&self, tok)));
                     continue;
                 }
-                i if i.is_ascii_whitespace() => continue,
-                '/' if self.peek.is_some_and(|x| x == '/') => { while self.peek.is_some_and(|x| x != '\n') { next_break!() }; continue; }
+                i if i.is_ascii_whitespace() => {
+                    while self.peek.is_some_and(|x| x.is_ascii_whitespace() && x != '\n') { self.next(); }
+                    self.trivia(TriviaKind::Whitespace, self.start); continue;
+                }
+                '/' if self.peek.is_some_and(|x| x == '/') => { 
+                    while self.peek.is_some_and(|x| x != '\n') { next_break!() }
+                    self.trivia(TriviaKind::LineComment, self.start); continue;
+                }
                 '/' if self.peek.is_some_and(|x| x == '*') => { 
-                    let (row, col) = (self.row, self.col);
-                    while self.peek.is_some_and(|x| x != '*') && self.peek_more.is_some_and(|x| x != '/') {
-                        self.next_err(CompErrID::UnterminatedComment, format!("Missing closing */ for multi-line comment started at {row}::{col}"))?;
-                    }; continue; 
+                    if let Err(err) = self.block_comment() { self.errors.push(err) }
+                    self.trivia(TriviaKind::BlockComment, self.start); continue; 
                 }
 
-                '(' => res.push(Token::make(&self, LParen, None)),
-                ')' => res.push(Token::make(&self, RParen, None)),
-                '{' => res.push(Token::make(&self, LBrace, None)),
-                '}' => res.push(Token::make(&self, RBrace, None)),
-                '[' => res.push(Token::make(&self, LSquare, None)),
-                ']' => res.push(Token::make(&self, RSquare, None)),
-                ':' => res.push(Token::make(&self, Colon, None)),
-                ';' => res.push(Token::make(&self, SemiColon, None)),
-                ',' => res.push(Token::make(&self, Comma, None)),
-                '#' => res.push(Token::make(&self, Hash, None)),
-                '$' => res.push(Token::make(&self, Dollar, None)),
-                '~' => res.push(Token::make(&self, Tilde, None)),
-
-                '.' => res.push(Token::make(&self, Dot, None)),
-                '+' => res.push(self.make_double('=', Plus, PlusEq)),
+                '(' => { brackets += 1; res.push(Token::make(&self, LParen)) }
+                ')' => { brackets = brackets.saturating_sub(1); res.push(Token::make(&self, RParen)) }
+                '{' => { brackets += 1; res.push(Token::make(&self, LBrace)) }
+                '}' => { brackets = brackets.saturating_sub(1); res.push(Token::make(&self, RBrace)) }
+                '[' => { brackets += 1; res.push(Token::make(&self, LSquare)) }
+                ']' => { brackets = brackets.saturating_sub(1); res.push(Token::make(&self, RSquare)) }
+                ':' => res.push(Token::make(&self, Colon)),
+                ';' => res.push(Token::make(&self, SemiColon)),
+                ',' => res.push(Token::make(&self, Comma)),
+                '#' => res.push(Token::make(&self, Hash)),
+                '$' => res.push(Token::make(&self, Dollar)),
+                '~' => res.push(Token::make(&self, Tilde)),
+
+                '.' => res.push(Token::make(&self, Dot)),
+                '+' => res.push(self.make_choice(Plus, &[('=', PlusEq), ('%', WrapPlus), ('|', SatPlus)])),
                 '-' => res.push(
                     match self.peek { 
-                        Some('=') => { let mut a = Token::make(&self, MinusEq, None); a.pos.1 -= 1; self.next(); a }
-                        Some('>') => { let mut a = Token::make(&self, Arrow, None); a.pos.1 -= 1; self.next(); a }
-                        _ => Token::make(&self, Minus, None),
+                        Some('=') => { self.next(); Token::make(&self, MinusEq) }
+                        Some('>') => { self.next(); Token::make(&self, Arrow) }
+                        Some('%') => { self.next(); Token::make(&self, WrapMinus) }
+                        Some('|') => { self.next(); Token::make(&self, SatMinus) }
+                        _ => Token::make(&self, Minus),
                     }),
                 '/' => res.push(self.make_double('=', Slash, SlashEq)),
-                '*' => res.push(self.make_double('=', Star, StarEq)),
+                '*' => res.push(self.make_choice(Star, &[('=', StarEq), ('%', WrapStar), ('|', SatStar)])),
 
-                '&' => res.push(Token::make(&self, Ampersand, None)),
-                '^' => res.push(Token::make(&self, Caret, None)),
+                '&' => res.push(Token::make(&self, Ampersand)),
+                '^' => res.push(Token::make(&self, Caret)),
                 '|' => res.push(self.make_double('>', Bar, Pipe)),
 
                 '!' => res.push(self.make_double('=', Bang, BangEq)),
                 '=' => res.push(
                     match self.peek { 
-                        Some('=') => { let mut a = Token::make(&self, EqEq, None); a.pos.1 -= 1; self.next(); a }
-                        Some('>') => { let mut a = Token::make(&self, ArrowEq, None); a.pos.1 -= 1; self.next(); a }
-                        _ => Token::make(&self, Eq, None),
+                        Some('=') => { self.next(); Token::make(&self, EqEq) }
+                        Some('>') => { self.next(); Token::make(&self, ArrowEq) }
+                        _ => Token::make(&self, Eq),
                     }),
                 '>' => res.push(self.make_double('=', More, MoreEq)),
                 '<' => res.push(self.make_double('=', Less, LessEq)),
 
-                '"' => self.string(&mut res)?,
-                '\'' => self.char(&mut res)?,
-                '`' => res.push(Token::make(&self, Backtick, None)),
-                c if c.is_ascii_digit() => self.number(&mut res, c),
-                c if c.is_ascii_alphabetic() || c == '_' => self.identifier(&mut res, c, &keywords)?,
-                _ => return Err(PhoenixError::Compile { id: CompErrID::InvalidCharacter, row: self.row, col: self.col, msg: format!("Invalid character {c} at {}::{}", self.row, self.col) })
+                '"' => recover!(self.string(&mut res)),
+                '\'' => recover!(self.char(&mut res)),
+                '`' => res.push(Token::make(&self, Backtick)),
+                'r' if matches!(self.peek, Some('"' | '#')) => recover!(self.raw_string(&mut res)),
+                c if c.is_ascii_digit() => recover!(self.number(&mut res, c)),
+                c if c == '_' || c.is_xid_start() => recover!(self.identifier(&mut res, &keywords)),
+                _ => self.recover(&mut res, PhoenixError::Compile { id: CompErrID::InvalidCharacter, span: self.last_span(), msg: format!("Invalid character {c}") }),
+            }
+        }
+        (res, self.symbols, self.trivia.unwrap_or_default(), self.errors)
+    }
+
+
+    /// Skips a block comment whose `/` was just consumed, nested comments must be closed before the outer one
+    fn block_comment(&mut self) -> Result<(), PhoenixError> {
+        let mut openers = vec![self.offset - 1];
+        self.next();
+        while let Some(opener) = openers.last().copied() {
+            match (self.next(), self.peek) {
+                (Some('/'), Some('*')) => { openers.push(self.prev); self.next(); }
+                (Some('*'), Some('/')) => { openers.pop(); self.next(); }
+                (None | Some('\0'), _) => return Err(PhoenixError::Compile { id: CompErrID::UnterminatedComment, span: Span::new(self.file, opener, opener + 2),
+                    msg: format!("Missing closing */ for this multi-line comment") }),
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the rest of the line from `peek` holds nothing but whitespace and comments, looking ahead without consuming
+    fn comment_only_line(&self) -> bool {
+        let mut rest = &self.src.as_str()[(self.offset - self.base) as usize..];
+        loop {
+            rest = rest.trim_start_matches([' ', '\t']);
+            if !rest.starts_with("/*") { break }
+            // Nested like `block_comment`, an unterminated comment runs to the end of the source
+            let mut depth = 0;
+            let mut chars = rest.char_indices().peekable();
+            while let Some((at, c)) = chars.next() {
+                match (c, chars.peek().map(|x| x.1)) {
+                    ('/', Some('*')) => { depth += 1; chars.next(); }
+                    ('*', Some('/')) => { depth -= 1; chars.next(); if depth == 0 { rest = &rest[at + 2..]; break } }
+                    _ => (),
+                }
             }
+            if depth > 0 { return true }
         }
-        Ok(res)
+        rest.is_empty() || rest.starts_with(['\n', '\r', '\0']) || rest.starts_with("//")
     }
 
+    fn indent(&self) -> u16 { *self.indents.last().unwrap() }
+
+    /// Level of a line indented with `spaces` and `tabs`, checked against the project style and the enclosing blocks
+    fn indent_level(&mut self, spaces: u16, tabs: u16, span: Span) -> Result<u16, PhoenixError> {
+        let err = |msg| Err(PhoenixError::Compile { id: CompErrID::InvalidIndentation, span, msg });
+        let level = match self.indent_style {
+            _ if spaces > 0 && tabs > 0 => return err(format!("Indentation mixes tabs and spaces")),
+            Indent::Spaces(width) if tabs > 0 => return err(format!("Indentation must use {width} spaces per level, found a tab")),
+            Indent::Spaces(width) if spaces % width as u16 != 0 => return err(format!("Indentation of {spaces} spaces is not a multiple of {width}")),
+            Indent::Spaces(width) => spaces / width as u16,
+            Indent::Tabs if spaces > 0 => return err(format!("Indentation must use tabs, found spaces")),
+            Indent::Tabs => tabs,
+        };
+
+        if level > self.indent() { self.indents.push(level) }
+        while level < self.indent() { self.indents.pop(); }
+        if level != self.indent() {
+            return err(format!("Indentation does not match any enclosing block, expected level {} or less", self.indent()))
+        }
+        Ok(level)
+    }
+
+    /// Character produced by the escape sequence following a `\\`
+    fn escape(&mut self, unterminated: CompErrID, msg: &str) -> Result<char, PhoenixError> {
+        let start = self.prev;
+        macro_rules! err { ($($arg:tt)*) => { Err(PhoenixError::Compile { id: CompErrID::InvalidEscape, span: Span::new(self.file, start, self.offset), msg: format!($($arg)*) }) }; }
+        let c = match self.next_err(unterminated, msg.to_owned())? {
+            'n' => '\n', 'r' => '\r', 't' => '\t', '0' => '\0',
+            c @ ('\\' | '"' | '\'' | '{' | '}') => c,
+            'x' => {
+                let hex = [self.next_err(unterminated, msg.to_owned())?, self.next_err(unterminated, msg.to_owned())?];
+                match u8::from_str_radix(&std::string::String::from_iter(hex), 16) {
+                    Ok(v) if v <= 0x7F => v as char,
+                    Ok(_) => return err!("'\\x{}{}' is not ASCII, use '\\u{{..}}' for other characters", hex[0], hex[1]),
+                    Err(_) => return err!("'\\x' must be followed by two hexadecimal digits"),
+                }
+            }
+            'u' => {
+                if self.peek != Some('{') { return err!("'\\u' must be followed by '{{'") }
+                self.next();
+                let mut hex = std::string::String::new();
+                while let Some(c) = self.peek.filter(|c| *c != '}' && *c != '"' && *c != '\n') { hex.push(c); self.next(); }
+                self.expect_char('}')?;
+                match u32::from_str_radix(&hex, 16) {
+                    Ok(v) if hex.len() <= 6 => match char::from_u32(v) {
+                        Some(c) => c,
+                        None => return err!("'\\u{{{hex}}}' is not a valid unicode scalar value"),
+                    }
+                    _ => return err!("'\\u{{..}}' must contain 1 to 6 hexadecimal digits, found '{hex}'"),
+                }
+            }
+            c => return err!("Unknown escape sequence '\\{c}'"),
+        };
+        Ok(c)
+    }
 
+    /// Plain strings become a single `String` token, interpolated ones `"a {x} b {y} c"` become
+    /// `InterpStart("a ")`, the tokens of `x`, `InterpMid(" b ")`, the tokens of `y`, `InterpEnd(" c")`
     fn string(&mut self, res: &mut Vec<Token>) -> Result<(), PhoenixError> {
-        macro_rules! next_string { () => { self.next_err(CompErrID::UnterminatedString, format!("Missing closing \" for string started at {}::{}", self.row, self.col)) };}
-        let mut str = String::new();
-        let pos = (self.row, self.col - 1);
+        let msg = format!("Missing closing \" for string");
+        macro_rules! next_string { () => { self.next_err(CompErrID::UnterminatedString, msg.clone()) };}
+        let mut str = std::string::String::new();
+        let mut part_start = self.start;
+        let mut interpolated = false;
         loop {
             let mut c = next_string!()?;
-            if c == '"' { break }
             match c {
                 '"' => break,
-                '\\' => c = match next_string!()? { 'n' => '\n', 'r' => '\r', 't' => '\t', '0' => '\0', p => p },
+                '\\' => match self.escape(CompErrID::UnterminatedString, &msg) {
+                    Ok(escaped) => c = escaped,
+                    Err(err @ PhoenixError::Compile { id: CompErrID::InvalidEscape, .. }) => { self.errors.push(err); continue }
+                    Err(err) => return Err(err),
+                },
+                '{' => {
+                    let sym = self.symbols.intern(&str);
+                    res.push(Token::make_sym(if interpolated { InterpMid } else { InterpStart }, Span::new(self.file, part_start, self.offset), sym));
+                    self.interpolation(res)?;
+                    str.clear(); part_start = self.prev; interpolated = true;
+                    continue;
+                }
+                _ => {}
+            }
+
+            str.push(c);
+            if c == '\n' { let skip = self.indent() as u32 * match self.indent_style { Indent::Spaces(width) => width as u32, Indent::Tabs => 1 }; for _ in 0..skip { next_string!()?;}}
+        }
+        let sym = self.symbols.intern(&str);
+        res.push(Token::make_sym(if interpolated { InterpEnd } else { String }, Span::new(self.file, part_start, self.offset), sym));
+        Ok(())
+    }
+
+    /// Scans the expression of an interpolation up to its closing `}` with a scanner of its own
+    fn interpolation(&mut self, res: &mut Vec<Token>) -> Result<(), PhoenixError> {
+        let brace = self.last_span();
+        let err = |id, msg| PhoenixError::Compile { id, span: brace, msg };
+        let mut src = std::string::String::new();
+        let (mut depth, mut in_str) = (0, false);
+        loop {
+            let c = match self.next() {
+                None | Some('\0') => return Err(err(CompErrID::UnterminatedString, format!("Missing closing }} for interpolation"))),
+                Some('\n') => return Err(PhoenixError::Compile { id: CompErrID::InvalidCharacter, span: self.last_span(), msg: format!("Interpolated expressions must fit on a single line") }),
+                Some(c) => c,
+            };
+            match c {
+                '"' => in_str = !in_str,
+                '\\' if in_str => { src.push(c); if let Some(c) = self.next() { src.push(c) }; continue }
+                '{' if !in_str => depth += 1,
+                '}' if !in_str && depth == 0 => break,
+                '}' if !in_str => depth -= 1,
                 _ => {}
             }
+            src.push(c);
+        }
+        if src.trim().is_empty() { return Err(err(CompErrID::InvalidCharacter, format!("Empty interpolation in string, use '\\{{' for a literal brace"))) }
+
+        let mut scanner = Scanner::new(src, self.file).with_max_identifier_length(self.max_identifier_length).with_indent(self.indent_style);
+        scanner.offset = brace.end; scanner.base = brace.end;
+        scanner.symbols = std::mem::take(&mut self.symbols);
+        scanner.trivia = self.trivia.as_ref().map(|_| vec![]);
+        let (mut tokens, symbols, trivia, errors) = scanner.run();
+        tokens.pop(); // Eof
+        res.extend(tokens); self.symbols = symbols; self.errors.extend(errors);
+        if let Some(pieces) = &mut self.trivia { pieces.extend(trivia) }
+        Ok(())
+    }
+
+    /// `r"..."` or `r#"..."#` with any number of `#`, the content is taken verbatim
+    fn raw_string(&mut self, res: &mut Vec<Token>) -> Result<(), PhoenixError> {
+        let mut hashes = 0;
+        while self.peek == Some('#') { hashes += 1; self.next(); }
+        self.expect_char('"')?;
 
+        let mut str = std::string::String::new();
+        loop {
+            let c = self.next_err(CompErrID::UnterminatedString, format!("Missing closing \"{} for raw string", "#".repeat(hashes)))?;
+            if c == '"' {
+                let mut closing = 0;
+                while closing < hashes && self.peek == Some('#') { closing += 1; self.next(); }
+                if closing == hashes { break }
+                str.push(c); str.extend(std::iter::repeat('#').take(closing));
+                continue;
+            }
             str.push(c);
-            if c == '\n' { let skip = self.indent as u32 * 4; for _ in 0..skip { next_string!()?;}}
         }
-        res.push(Token::make_pos(String, Some(&*str), pos));
+        let sym = self.symbols.intern(&str);
+        res.push(Token::make_sym(String, self.span(), sym));
         Ok(())
     }
 
     fn char(&mut self, res: &mut Vec<Token>) -> Result<(), PhoenixError> {
-        macro_rules! next_char { () => { self.next_err(CompErrID::UnterminatedChar, format!("Missing closing ' for string started at {}::{}", self.row, self.col)) };}
+        let msg = format!("Missing closing ' for char");
+        macro_rules! next_char { () => { self.next_err(CompErrID::UnterminatedChar, msg.clone()) };}
 
-        let pos = (self.row, self.col - 1);
         let mut c = next_char!()?;
 
-        if c == '\'' { return Err(PhoenixError::Compile { id: CompErrID::InvalidCharLiteral, row: self.row, col: self.row, msg: format!("Char literal cannot be empty") }); }
+        if c == '\'' { return Err(PhoenixError::Compile { id: CompErrID::InvalidCharLiteral, span: self.span(), msg: format!("Char literal cannot be empty") }); }
 
         match c {
-            '\\' => c = match next_char!()? { 'n' => '\n', 'r' => '\r', 't' => '\t', '0' => '\0', p => p },
-            '\n' | '\r' | '\t' => return Err(PhoenixError::Compile { id: CompErrID::InvalidCharacter, row: self.row, col: self.col, msg: format!("Cannot use special characters in char literals") }),
+            '\\' => match self.escape(CompErrID::UnterminatedChar, &msg) {
+                Ok(escaped) => c = escaped,
+                Err(err @ PhoenixError::Compile { id: CompErrID::InvalidEscape, .. }) => { self.errors.push(err); c = char::REPLACEMENT_CHARACTER }
+                Err(err) => return Err(err),
+            },
+            '\n' | '\r' | '\t' => return Err(PhoenixError::Compile { id: CompErrID::InvalidCharacter, span: self.last_span(), msg: format!("Cannot use special characters in char literals") }),
             _ => {}
         }
 
         self.expect_char('\'')?;
 
-        res.push(Token::make_pos(Char, Some(c.encode_utf8(&mut [0])), pos));
+        let sym = self.symbols.intern(c.encode_utf8(&mut [0; 4]));
+        res.push(Token::make_sym(Char, self.span(), sym));
         Ok(())
     }
 
-    fn number(&mut self, res: &mut Vec<Token>, c: char) {
-        let pos = (self.row, self.col - 1);
-        let mut str = String::from(c);
-        let mut dot = false;
-        loop {
-            let c = match self.peek { Some('\0') | None => break, Some(c) => c, };
-            match &c {
-                '.' => if dot { break; } else { dot = true; }
-                c if c.is_ascii_digit() => {}
-                _ => break,
-            }
-            str.push(c);
+    /// Decimal `1_000`, `6.02e23`, radix prefixed `0xFF`, `0o17`, `0b1010`, all optionally followed by a type suffix like `u8`.
+    /// Only the shape is checked here, values are parsed and range checked when the literal is compiled.
+    fn number(&mut self, res: &mut Vec<Token>, c: char) -> Result<(), PhoenixError> {
+        macro_rules! number_err { ($span:expr, $($arg:tt)*) => { Err(PhoenixError::Compile { id: CompErrID::InvalidNumber, span: $span, msg: format!($($arg)*) }) }; }
+        let is_suffix = |s: &Self| s.peek.is_some_and(|c| ['i', 'u', 'f'].contains(&c)) && s.peek_more.is_some_and(|c| c.is_ascii_digit());
+        let mut dec = false;
+
+        let radix = match (c, self.peek) { ('0', Some('x')) => 16, ('0', Some('o')) => 8, ('0', Some('b')) => 2, _ => 10 };
+        if radix != 10 {
             self.next();
+            let mut digits = 0;
+            while let Some(c) = self.peek.filter(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                if c != 'f' && is_suffix(self) { break }
+                if !c.is_digit(radix) && c != '_' { return number_err!(self.peek_span(), "Invalid digit '{c}' in base {radix} literal") }
+                if c != '_' { digits += 1 }
+                self.next();
+            }
+            if digits == 0 { return number_err!(self.peek_span(), "Missing digits after '{}'", self.text(self.start)) }
+        } else {
+            let mut dot = false;
+            loop {
+                let c = match self.peek { Some('\0') | None => break, Some(c) => c, };
+                match &c {
+                    '.' if !dot && self.peek_more.is_some_and(|c| c.is_ascii_digit()) => dot = true,
+                    '_' => {}
+                    c if c.is_ascii_digit() => {}
+                    _ => break,
+                }
+                self.next();
+            }
+            dec = dot;
+
+            // Exponent, `e` is only part of the literal if a digit or a sign follows it
+            if self.peek.is_some_and(|c| c == 'e' || c == 'E') && self.peek_more.is_some_and(|c| c.is_ascii_digit() || c == '+' || c == '-') {
+                dec = true;
+                self.next();
+                if self.peek.is_some_and(|c| c == '+' || c == '-') { self.next(); }
+                if !self.peek.is_some_and(|c| c.is_ascii_digit()) { return number_err!(self.peek_span(), "Missing digits in the exponent of '{}'", self.text(self.start)) }
+                while self.peek.is_some_and(|c| c.is_ascii_digit() || c == '_') { self.next(); }
+            }
+        }
+
+        // Type suffix, e.g. `255u8` or `1.5f32`
+        if is_suffix(self) {
+            while self.peek.is_some_and(|c| c.is_ascii_alphanumeric()) { self.next(); }
         }
-        res.push(Token::make_pos(if dot { Dec } else { Int }, Some(&*str), pos));
+        res.push(Token::make(&self, if dec { Dec } else { Int }));
+        Ok(())
     }
     
-    fn identifier(&mut self, res: &mut Vec<Token>, c: char, keywords: &AHashMap<&'static str, TokenType>) -> Result<(), PhoenixError> {
-        let pos = (self.row, self.col - 1);
-        let mut str = String::from(c);
+    /// Identifiers follow the Unicode XID rules and are NFC normalized, so differently encoded spellings of a name are the same symbol
+    fn identifier(&mut self, res: &mut Vec<Token>, keywords: &AHashMap<&'static str, TokenType>) -> Result<(), PhoenixError> {
         let mut can_be_type = true;
         loop {
             let c = match self.peek { Some('\0') | None => break, Some(c) => c, };
             match &c {
                 '?' | '!'  => can_be_type = false,
-                c if c.is_ascii_digit() => can_be_type = false, 
-                '_' => {}
-                c if c.is_ascii_alphabetic() => {}
+                // Names holding digits, like those holding `?` or `!`, are never keywords nor type names
+                c if c.is_xid_continue() => if c.is_numeric() { can_be_type = false },
+                // Anything else outside XID, numeric characters like `½` included, ends the identifier
                 _ => break,
             }
-
-            if str.len() >= IDENTIFIER_MAX_LENGTH { return Err(PhoenixError::Compile { id: CompErrID::IdentifierTooLong, row: self.row, col: self.col, 
-                    msg: format!("A type or an identifier can have a maximum length of {}", IDENTIFIER_MAX_LENGTH)})}
-            str.push(c);
             self.next();
         }
+        // ASCII is always in normal form, only other names need a normalized copy
+        let name = &self.src.as_str()[(self.start - self.base) as usize..(self.offset - self.base) as usize];
+        let normalized: Option<String> = if name.is_ascii() { None } else { Some(name.nfc().collect()) };
+        let name = normalized.as_deref().unwrap_or(name);
+
+        let len = name.chars().count();
+        if let Some(max) = self.max_identifier_length.filter(|max| len > *max) {
+            return Err(PhoenixError::Compile { id: CompErrID::IdentifierTooLong, span: self.span(),
+                msg: format!("A type or an identifier can have a maximum length of {max}, '{name}' is {len} characters long") })
+        }
         
-        let is_keyword = if can_be_type { keywords.get(&*str) } else { None };
-        let str = format!("{}{str}", can_be_type as u8);
-        res.push(Token::make_pos(if is_keyword.is_some() { *is_keyword.unwrap() } else { Identifier }, if is_keyword.is_none() { Some(&str) } else { None }, pos));
+        if let Some(keyword) = keywords.get(name).filter(|_| can_be_type) { res.push(Token::make(&self, *keyword)); return Ok(()) }
+        let sym = self.symbols.intern(name);
+        res.push(Token { can_be_type, ..Token::make_sym(Identifier, Span::new(self.file, self.start, self.offset), sym) });
         Ok(())
     }
 }
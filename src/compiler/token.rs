@@ -1,15 +1,78 @@
 use std::collections::HashMap;
+use std::hash::BuildHasherDefault;
+use std::num::NonZeroU32;
 use std::string::String;
-use std::ops::RangeBounds;
+use std::ops::{RangeBounds, Range};
+use std::sync::Arc;
+use ahash::AHasher;
 use TokenType::*;
 
 use crate::compiler::scanner::Scanner;
+use crate::source::Span;
 
-#[derive(Debug, Clone)]
+/// Tokens only point into the source, the text of numbers and punctuation is read back from their span
+#[derive(Debug, Clone, Copy)]
 pub struct Token {
     pub ty: TokenType,
-    pub lexeme: Option<Box<str>>,
-    pub pos: (u16, u16)
+    /// Identifier without digits, `?` or `!`, which may also name a type
+    pub can_be_type: bool,
+    pub span: Span,
+    /// Interned name of identifiers, and content of string and char literals once escapes are resolved
+    pub sym: Option<Symbol>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriviaKind { Whitespace, Newline, LineComment, BlockComment }
+
+/// Source text that is not part of any token, only produced by a lossless scan
+#[derive(Clone, Copy, Debug)]
+pub struct Trivia { pub kind: TriviaKind, pub span: Span }
+
+/// Trivia of a lossless scan grouped by the token that follows it, trailing trivia belongs to `Eof`
+#[derive(Debug, Default)]
+pub struct TriviaMap {
+    pieces: Vec<Trivia>,
+    leading: Vec<Range<u32>>,
+}
+
+impl TriviaMap {
+    pub fn new(tokens: &[Token], pieces: Vec<Trivia>) -> Self {
+        let mut next = 0;
+        let leading = tokens.iter().map(|token| {
+            let start = next;
+            while pieces.get(next).is_some_and(|piece| piece.span.start < token.span.start) { next += 1 }
+            start as u32..next as u32
+        }).collect();
+        Self { pieces, leading }
+    }
+
+    /// Trivia right before the token at `index`
+    pub fn leading(&self, index: usize) -> &[Trivia] {
+        let Range { start, end } = self.leading[index].clone();
+        &self.pieces[start as usize..end as usize]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Symbol(NonZeroU32);
+
+/// Identifiers and literal contents of a file, every distinct string is stored once
+#[derive(Default)]
+pub struct Symbols {
+    strs: Vec<Arc<str>>,
+    ids: HashMap<Arc<str>, Symbol, BuildHasherDefault<AHasher>>,
+}
+
+impl Symbols {
+    pub fn intern(&mut self, str: &str) -> Symbol {
+        if let Some(sym) = self.ids.get(str) { return *sym }
+        let str = Arc::<str>::from(str);
+        self.strs.push(str.clone());
+        let sym = Symbol(NonZeroU32::new(self.strs.len() as u32).unwrap());
+        self.ids.insert(str, sym); sym
+    }
+
+    pub fn get(&self, sym: Symbol) -> &Arc<str> { &self.strs[sym.0.get() as usize - 1] }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -21,28 +84,43 @@ pub enum TokenType {
     Minus, MinusEq, Arrow,
     Star, StarEq, 
     Slash, SlashEq,
+    WrapPlus, WrapMinus, WrapStar,
+    SatPlus, SatMinus, SatStar,
 
     Ampersand, Caret, Bar, Pipe,
     Hash, Dollar, Tilde, Backtick,
     IndentUp, IndentDown, 
+    /// End of a logical line, not produced inside brackets or after a binary operator
+    Newline,
 
     Bang, BangEq,
     Eq, EqEq, ArrowEq,
     More, MoreEq,
     Less, LessEq,
 
-    Identifier, String, Int, Dec, Char,
+    Identifier, String, InterpStart, InterpMid, InterpEnd, Int, Dec, Char,
 
-    And, Alias, As, Else, False, Fn, If, Infix, Let, Loop,
+    And, Alias, As, Const, Else, False, Fn, If, Infix, Let, Loop,
     Macro, Mod, Mut, Not, Or, Print, Pub, Return, Selff,
 
     Struct, Super, Trait, True, While, Xor,
+    /// Source the scanner could not tokenize, its error is reported by the scanner
+    Error,
     Eof,
 }
 
 impl Token {
-    pub fn make(scanner: &Scanner, ty: TokenType, lexeme: Option<&str>) -> Token { Token { ty, lexeme: lexeme.map(|str| str.into()), pos: (scanner.row, scanner.col) }}
-    pub fn make_pos(ty: TokenType, lexeme: Option<&str>, pos: (u16, u16)) -> Token { Token { ty, lexeme: lexeme.map(|str| str.into()), pos } }
+    /// Token spanning everything the scanner consumed since the token started
+    pub fn make(scanner: &Scanner, ty: TokenType) -> Token { Token { ty, can_be_type: false, span: scanner.span(), sym: None }}
+    pub fn make_sym(ty: TokenType, span: Span, sym: Symbol) -> Token { Token { ty, can_be_type: false, span, sym: Some(sym) } }
+}
+
+impl TokenType {
+    /// Binary operators, assignments, `as`, `,` and `.`, a line ending with one continues on the next line
+    pub fn continues_line(self) -> bool {
+        matches!(self, Plus | PlusEq | Minus | MinusEq | Star | StarEq | Slash | SlashEq | WrapPlus | WrapMinus | WrapStar | SatPlus | SatMinus | SatStar
+            | Ampersand | Caret | Bar | Pipe | BangEq | Eq | EqEq | More | MoreEq | Less | LessEq | And | Or | Xor | As | Comma | Dot)
+    }
 }
 
 
@@ -0,0 +1,173 @@
+use std::{fmt::Display, str::FromStr};
+
+/// Maximum number of digits after the point, results needing more are rounded half to even
+pub const MAX_SCALE: u8 = 28;
+
+/// Exact base 10 number backing the `Dec` type, its value is `coef * 10^-scale`.
+/// Addition, subtraction and multiplication keep every digit, division stops at [`MAX_SCALE`] digits.
+/// Any operation whose coefficient would not fit in an i128 fails instead of losing precision.
+///
+/// The coefficient is only aligned to 8 bytes and the scale can only hold valid values, so a `Value::Dec`
+/// takes 24 bytes and the enum keeps its other variants in the scale values left unused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(packed(8))]
+pub struct Decimal { coef: i128, scale: Scale }
+
+/// Number of digits after the point, from 0 to [`MAX_SCALE`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+enum Scale { S0, S1, S2, S3, S4, S5, S6, S7, S8, S9, S10, S11, S12, S13, S14, S15, S16, S17, S18, S19, S20, S21, S22, S23, S24, S25, S26, S27, S28 }
+
+impl From<u8> for Scale {
+    fn from(v: u8) -> Self {
+        use Scale::*;
+        const ALL: [Scale; MAX_SCALE as usize + 1] = [S0, S1, S2, S3, S4, S5, S6, S7, S8, S9, S10, S11, S12, S13, S14, S15, S16, S17, S18, S19, S20, S21, S22, S23, S24, S25, S26, S27, S28];
+        ALL[v as usize]
+    }
+}
+
+impl std::fmt::Debug for Scale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { write!(f, "{}", *self as u8) }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseDecimalError;
+
+fn pow10(exp: u32) -> Option<i128> { 10i128.checked_pow(exp) }
+
+/// Rounds `coef * 10^-scale` to `scale - digits`, half to even
+fn round_off(coef: i128, digits: u8) -> i128 {
+    let div = pow10(digits as u32).unwrap();
+    let (quot, rem) = (coef / div, (coef % div).abs());
+    if rem * 2 > div || (rem * 2 == div && quot % 2 != 0) { quot + coef.signum() } else { quot }
+}
+
+/// Magnitude of `a * b / div` rounded half to even, computed over the whole 256 bit product, `None` if it needs more than 128 bits
+fn mul_div_round(a: u128, b: u128, div: u128) -> Option<u128> {
+    const LOW: u128 = u64::MAX as u128;
+    let (a1, a0, b1, b0) = (a >> 64, a & LOW, b >> 64, b & LOW);
+    let (low, cross_a, cross_b, high) = (a0 * b0, a0 * b1, a1 * b0, a1 * b1);
+    let mid = (low >> 64) + (cross_a & LOW) + (cross_b & LOW);
+    let (hi, lo) = (high + (cross_a >> 64) + (cross_b >> 64) + (mid >> 64), (low & LOW) | (mid << 64));
+
+    // Long division one bit at a time, `div` is a power of 10 small enough for the remainder to never overflow
+    let (mut quot_hi, mut quot, mut rem) = (0u128, 0u128, 0u128);
+    for bit in (0..256).rev() {
+        rem = rem << 1 | if bit >= 128 { hi >> (bit - 128) & 1 } else { lo >> bit & 1 };
+        let digit = rem >= div;
+        if digit { rem -= div }
+        quot_hi = quot_hi << 1 | quot >> 127;
+        quot = quot << 1 | digit as u128;
+    }
+    if quot_hi != 0 { return None }
+    if rem * 2 > div || (rem * 2 == div && quot % 2 != 0) { quot.checked_add(1) } else { Some(quot) }
+}
+
+impl Decimal {
+    fn parts(coef: i128, scale: u8) -> Self { Self { coef, scale: Scale::from(scale) } }
+
+    fn scale(self) -> u8 { self.scale as u8 }
+
+    pub fn from_int(v: i128) -> Self { Self::parts(v, 0) }
+
+    fn new_rounded(coef: i128, scale: u8) -> Self {
+        if scale <= MAX_SCALE { Self::parts(coef, scale) } else { Self::parts(round_off(coef, scale - MAX_SCALE), MAX_SCALE) }
+    }
+
+    fn rescale(self, scale: u8) -> Option<i128> { self.coef.checked_mul(pow10((scale - self.scale()) as u32)?) }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        let scale = self.scale().max(rhs.scale());
+        Some(Self::parts(self.rescale(scale)?.checked_add(rhs.rescale(scale)?)?, scale))
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        let scale = self.scale().max(rhs.scale());
+        Some(Self::parts(self.rescale(scale)?.checked_sub(rhs.rescale(scale)?)?, scale))
+    }
+
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        let scale = self.scale() + rhs.scale();
+        if let Some(coef) = self.coef.checked_mul(rhs.coef) { return Some(Self::new_rounded(coef, scale)) }
+
+        // The exact product needs more than 128 bits, it may still fit once rounded to MAX_SCALE digits
+        let digits = scale.saturating_sub(MAX_SCALE);
+        let coef = mul_div_round(self.coef.unsigned_abs(), rhs.coef.unsigned_abs(), pow10(digits as u32)? as u128)?;
+        let coef = i128::try_from(coef).ok()?;
+        Some(Self::parts(if (self.coef < 0) != (rhs.coef < 0) { -coef } else { coef }, scale.min(MAX_SCALE)))
+    }
+
+    /// Long division, digits are produced until the remainder is zero, [`MAX_SCALE`] is reached
+    /// or the coefficient is full, the last one is then rounded half to even
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        if rhs.coef == 0 { return None }
+        let (num, div) = (self.coef.unsigned_abs(), rhs.coef.unsigned_abs());
+        let (mut quot, mut rem) = (num / div, num % div);
+        let mut scale = self.scale() as i32 - rhs.scale() as i32;
+
+        while rem != 0 && scale < MAX_SCALE as i32 {
+            let Some(rem10) = rem.checked_mul(10) else { break };
+            match quot.checked_mul(10).and_then(|q| q.checked_add(rem10 / div)).filter(|q| *q <= i128::MAX as u128) {
+                Some(next) => { quot = next; rem = rem10 % div; scale += 1; }
+                None => break,
+            }
+        }
+        if rem * 2 > div || (rem * 2 == div && quot % 2 != 0) { quot += 1 }
+
+        let mut coef = i128::try_from(quot).ok()?;
+        if scale < 0 { coef = coef.checked_mul(pow10(-scale as u32)?)?; scale = 0 }
+        if (self.coef < 0) != (rhs.coef < 0) { coef = -coef }
+        Some(Self::parts(coef, scale as u8))
+    }
+
+    pub fn is_zero(self) -> bool { self.coef == 0 }
+
+    pub fn checked_neg(self) -> Option<Self> { Some(Self::parts(self.coef.checked_neg()?, self.scale())) }
+
+    /// Integer part, rounded toward zero
+    pub fn trunc(self) -> i128 { self.coef / pow10(self.scale() as u32).unwrap() }
+
+    pub fn to_f64(self) -> f64 { self.to_string().parse().unwrap() }
+
+    /// Shortest decimal that prints as `f`, rounded to [`MAX_SCALE`] digits if it has more
+    pub fn from_f64(f: f64) -> Option<Self> {
+        if !f.is_finite() { return None }
+        f.to_string().parse().ok().or_else(|| format!("{f:.28}").trim_end_matches('0').parse().ok())
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = ParseDecimalError;
+
+    /// `-?[0-9]+(\.[0-9]*)?([eE][+-]?[0-9]+)?`, fails rather than rounding when the value has more than [`MAX_SCALE`] decimals
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (neg, digits) = match s.strip_prefix('-') { Some(digits) => (true, digits), None => (false, s) };
+        let (digits, exp) = match digits.split_once(['e', 'E']) {
+            Some((digits, exp)) => (digits, exp.strip_prefix('+').unwrap_or(exp).parse::<i32>().map_err(|_| ParseDecimalError)?),
+            None => (digits, 0),
+        };
+        let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+        let scale = frac.len() as i64 - exp as i64;
+        if int.is_empty() || scale > MAX_SCALE as i64 { return Err(ParseDecimalError) }
+
+        let mut coef: i128 = 0;
+        for c in int.chars().chain(frac.chars()) {
+            let digit = c.to_digit(10).ok_or(ParseDecimalError)? as i128;
+            coef = coef.checked_mul(10).and_then(|coef| coef.checked_add(digit)).ok_or(ParseDecimalError)?;
+        }
+        if scale < 0 {
+            let mul = u32::try_from(-scale).ok().and_then(pow10).ok_or(ParseDecimalError)?;
+            coef = coef.checked_mul(mul).ok_or(ParseDecimalError)?;
+        }
+        Ok(Self::parts(if neg { -coef } else { coef }, scale.max(0) as u8))
+    }
+}
+
+impl Display for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let digits = self.coef.unsigned_abs().to_string();
+        let digits = format!("{digits:0>width$}", width = self.scale() as usize + 1);
+        let (int, frac) = digits.split_at(digits.len() - self.scale() as usize);
+        write!(f, "{}{int}.{}", if self.coef < 0 { "-" } else { "" }, if frac.is_empty() { "0" } else { frac })
+    }
+}
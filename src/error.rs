@@ -2,34 +2,56 @@ use std::fmt::{Display, write, Debug};
 
 use clap::error::ErrorKind;
 
+use crate::source::{SourceMap, Span};
+
 
 
 //#[derive(Debug)]
 pub enum PhoenixError {
     Cli(ErrorKind, String),
     Config(String),
-    Compile { id: CompErrID, row: u16, col: u16, msg: String },
-    Runtime(String)
+    Compile { id: CompErrID, span: Span, msg: String },
+    Runtime(String),
+    /// The stack is full and cannot grow past `size` values, `depth` counts the call frames including the top level
+    StackOverflow { depth: usize, size: usize },
 }
 
 impl Debug for PhoenixError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            PhoenixError::Compile { id, row, col, msg } => write!(f, "Compile error of type {id:?} at {row}::{col}: \n{msg}"),
+            PhoenixError::Compile { id, span, msg } => write!(f, "Compile error of type {id:?} at {span:?}: \n{msg}"),
             PhoenixError::Runtime(msg) => write!(f, "Program panicked! {msg}"),
+            PhoenixError::StackOverflow { depth, size } => write!(f, "Program panicked! Stack overflow at call depth {depth}, the stack holds at most {size} values"),
             PhoenixError::Cli(_, msg) | PhoenixError::Config(msg) => write!(f, "{msg}"),
         }
     }
 }
 
-#[derive(Debug)]
+impl PhoenixError {
+    /// Compile errors are shown with their location and the spanned code underlined, other errors as their message
+    pub fn render(&self, sources: &SourceMap) -> String {
+        let PhoenixError::Compile { id, span, msg } = self else { return format!("{self:?}") };
+        let file = sources.get(span.file);
+        let location = sources.location(*span);
+        let line = file.line_text(location.row - 1);
+
+        // Spans over several lines are underlined up to the end of their first one
+        let end = if file.line(span.end.max(span.start + 1) - 1) == location.row - 1 { file.location(span.end).1 } else { line.chars().count() + 1 };
+        let gutter = " ".repeat(location.row.to_string().len());
+        format!("error[{id:?}]: {msg}\n{gutter}--> {location}\n{gutter} |\n{} | {line}\n{gutter} | {}{}",
+            location.row, " ".repeat(location.col - 1), "^".repeat(end.saturating_sub(location.col).max(1)))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
 pub enum CompErrID {
     // Feather.toml errors
     ConfigError,
     // Scanner errors
     InvalidCharacter, UnterminatedComment, UnterminatedString, UnterminatedChar, InvalidCharLiteral,
-    IdentifierTooLong, 
+    IdentifierTooLong, InvalidNumber, InvalidEscape, InvalidIndentation,
     // Compiler errors
-    TypeError, InvalidSymbol, UnknownSymbol,
+    TypeError, InvalidSymbol, UnknownSymbol, DuplicateSymbol, UnexpectedToken, NestingTooDeep,
+    NotConstant, ConstantOverflow, DivisionByZero,
     MissingGlobalSymbol,
 }
@@ -3,23 +3,106 @@ use std::cell::RefCell;
 use std::mem;
 use std::rc::Rc;
 use std::str::FromStr;
+use std::fmt::Write;
 use crate::vm::value::Pointer;
 use crate::vm::{value, Stack};
+use crate::error::PhoenixError;
 use crate::{op_codes, vm::{Vm, value::Value}, compiler::chunk::Const};
 
 op_codes! {
-    #[derive(PartialEq, Eq)]
+    #[derive(Clone, Copy, PartialEq, Eq)]
     pub enum FBOpCode {
         OpReturn = 0 => 1,
         OpConstant => 4, OpTrue => 1, OpFalse => 1,
-        OpPop => 1,
+        OpPop => 1, OpPopN => 2, OpPopUnder => 2,
         OpAdd => 1, OpSub => 1, OpMul => 1, OpDiv => 1, OpNeg => 1,
+        OpWrapAdd => 1, OpWrapSub => 1, OpWrapMul => 1,
+        OpSatAdd => 1, OpSatSub => 1, OpSatMul => 1,
+        OpAddInt => 1, OpSubInt => 1, OpMulInt => 1, OpDivInt => 1,
+        OpAddDec => 1, OpSubDec => 1, OpMulDec => 1, OpDivDec => 1,
+        OpConcat => 1, OpConcatChar => 1,
         OpPrint => 1,
+        OpCast => 2, OpFormat => 2,
         OpGlobSet => 4, OpGlobGet => 4, OpGlobClone => 4,
         OpLocSet => 4, OpLocGet => 4, OpLocClone => 4,
     }
 }
 
+/// Operand of `OpCast`, the type the value on top of the stack is converted to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CastTy { Int = 0, Dec, Char, Str, I8, I16, I32, I64, U8, U16, U32, U64, F32, F64 }
+
+impl std::convert::From<u8> for CastTy {
+    fn from(v: u8) -> Self {
+        match v {
+            0 => CastTy::Int, 1 => CastTy::Dec, 2 => CastTy::Char, 3 => CastTy::Str,
+            4 => CastTy::I8, 5 => CastTy::I16, 6 => CastTy::I32, 7 => CastTy::I64,
+            8 => CastTy::U8, 9 => CastTy::U16, 10 => CastTy::U32, 11 => CastTy::U64,
+            12 => CastTy::F32, 13 => CastTy::F64,
+            _ => unreachable!()
+        }
+    }
+}
+
+/// Arithmetic shared by every numeric type, integers and Decs are checked and fail at runtime when they overflow their width.
+/// Extra match arms are spliced after the numeric ones.
+macro_rules! arithmetic {
+    ($first:expr, $second:expr, $checked:ident, $op:tt, $what:literal $(, $pat:pat => $arm:expr)*) => {
+        match ($first, $second) {
+            (Value::Int(a), Value::Int(b)) => Value::Int(a.$checked(*b).ok_or_else(|| overflow("Int", $what))?),
+            (Value::F32(a), Value::F32(b)) => Value::F32(*a $op *b),
+            (Value::F64(a), Value::F64(b)) => Value::F64(*a $op *b),
+            (Value::Dec(a), Value::Dec(b)) => Value::Dec(a.$checked(*b).ok_or_else(|| overflow("Dec", $what))?),
+            (Value::I8(a), Value::I8(b)) => Value::I8(a.$checked(*b).ok_or_else(|| overflow("I8", $what))?),
+            (Value::I16(a), Value::I16(b)) => Value::I16(a.$checked(*b).ok_or_else(|| overflow("I16", $what))?),
+            (Value::I32(a), Value::I32(b)) => Value::I32(a.$checked(*b).ok_or_else(|| overflow("I32", $what))?),
+            (Value::I64(a), Value::I64(b)) => Value::I64(a.$checked(*b).ok_or_else(|| overflow("I64", $what))?),
+            (Value::U8(a), Value::U8(b)) => Value::U8(a.$checked(*b).ok_or_else(|| overflow("U8", $what))?),
+            (Value::U16(a), Value::U16(b)) => Value::U16(a.$checked(*b).ok_or_else(|| overflow("U16", $what))?),
+            (Value::U32(a), Value::U32(b)) => Value::U32(a.$checked(*b).ok_or_else(|| overflow("U32", $what))?),
+            (Value::U64(a), Value::U64(b)) => Value::U64(a.$checked(*b).ok_or_else(|| overflow("U64", $what))?),
+            $($pat => $arm,)*
+            (_, _) => unreachable!()
+        }
+    };
+}
+
+/// Integer only arithmetic that cannot fail, like the wrapping and saturating operators
+macro_rules! int_arithmetic {
+    ($first:expr, $second:expr, $method:ident) => {
+        match ($first, $second) {
+            (Value::Int(a), Value::Int(b)) => Value::Int(a.$method(*b)),
+            (Value::I8(a), Value::I8(b)) => Value::I8(a.$method(*b)),
+            (Value::I16(a), Value::I16(b)) => Value::I16(a.$method(*b)),
+            (Value::I32(a), Value::I32(b)) => Value::I32(a.$method(*b)),
+            (Value::I64(a), Value::I64(b)) => Value::I64(a.$method(*b)),
+            (Value::U8(a), Value::U8(b)) => Value::U8(a.$method(*b)),
+            (Value::U16(a), Value::U16(b)) => Value::U16(a.$method(*b)),
+            (Value::U32(a), Value::U32(b)) => Value::U32(a.$method(*b)),
+            (Value::U64(a), Value::U64(b)) => Value::U64(a.$method(*b)),
+            (_, _) => unreachable!()
+        }
+    };
+}
+
+/// Pops an operand the compiler proved to be a `Value::$variant`, pointers and upvalues are only followed
+/// when the value is not held directly, locals holding it are read in place
+macro_rules! operand {
+    ($vm:expr, $variant:ident) => {
+        match $vm.stack.pop()? {
+            Value::$variant(v) => v,
+            Value::Ptr(Pointer::Local(addr)) if matches!($vm.stack[addr], Value::$variant(_)) => match &$vm.stack[addr] { Value::$variant(v) => v.clone(), _ => unreachable!() },
+            value => match &**value.depoint($vm).deupvalue($vm) { Value::$variant(v) => v.clone(), _ => unreachable!() },
+        }
+    };
+}
+
+/// Little endian u24 operand starting at `at`, read in place
+#[inline(always)]
+fn u24(code: &[u8], at: usize) -> usize { code[at] as usize | (code[at + 1] as usize) << 8 | (code[at + 2] as usize) << 16 }
+
+fn overflow(ty: &str, what: &str) -> PhoenixError { PhoenixError::Runtime(format!("{ty} {what}")) }
+
 pub fn debug(i: u64, slice: &[u8]) {
     macro_rules! oper { ($op:literal $($arg:tt)*) => {{print!("{}", $op); println!($($arg)*);}}; }
 
@@ -27,176 +110,248 @@ pub fn debug(i: u64, slice: &[u8]) {
     match FBOpCode::from(slice[0]) {
         FBOpCode::OpReturn => oper!("OpReturn"),
         FBOpCode::OpConstant => {
-            let a = u32::from_le_bytes({let mut a = [0; 4]; a[0..3].copy_from_slice(&slice[1..]); a}); //u24
+            let a = u24(slice, 1);
             oper!("OpConstant" "\t#{}", a);
         }
-        FBOpCode::OpPop =>oper!("OpPop"), 
+        FBOpCode::OpPop =>oper!("OpPop"), FBOpCode::OpPopN => oper!("OpPopN" "\t{}", slice[1]),
+        FBOpCode::OpPopUnder => oper!("OpPopUnder" "\t{}", slice[1]),
         FBOpCode::OpTrue => oper!("OpTrue"), FBOpCode::OpFalse => oper!("OpFalse"),
         FBOpCode::OpAdd => oper!("OpAdd"), FBOpCode::OpSub => oper!("OpSub"),
+        FBOpCode::OpWrapAdd => oper!("OpWrapAdd"), FBOpCode::OpWrapSub => oper!("OpWrapSub"), FBOpCode::OpWrapMul => oper!("OpWrapMul"),
+        FBOpCode::OpSatAdd => oper!("OpSatAdd"), FBOpCode::OpSatSub => oper!("OpSatSub"), FBOpCode::OpSatMul => oper!("OpSatMul"),
         FBOpCode::OpMul => oper!("OpMul"), FBOpCode::OpDiv => oper!("OpDiv"),
+        FBOpCode::OpAddInt => oper!("OpAddInt"), FBOpCode::OpSubInt => oper!("OpSubInt"), FBOpCode::OpMulInt => oper!("OpMulInt"), FBOpCode::OpDivInt => oper!("OpDivInt"),
+        FBOpCode::OpAddDec => oper!("OpAddDec"), FBOpCode::OpSubDec => oper!("OpSubDec"), FBOpCode::OpMulDec => oper!("OpMulDec"), FBOpCode::OpDivDec => oper!("OpDivDec"),
+        FBOpCode::OpConcat => oper!("OpConcat"), FBOpCode::OpConcatChar => oper!("OpConcatChar"),
         FBOpCode::OpNeg => oper!("OpNeg"),
         FBOpCode::OpPrint => oper!("OpPrint"),
+        FBOpCode::OpCast => oper!("OpCast" "\t\t{:?}", CastTy::from(slice[1])),
+        FBOpCode::OpFormat => oper!("OpFormat" "\t{}", slice[1]),
         FBOpCode::OpGlobSet => {
-            let a = u32::from_le_bytes({let mut a = [0; 4]; a[0..3].copy_from_slice(&slice[1..]); a}); //u24
+            let a = u24(slice, 1);
             oper!("OpGlobSet\t->" "\t#{}", a);
         }
         FBOpCode::OpGlobGet => {
-            let a = u32::from_le_bytes({let mut a = [0; 4]; a[0..3].copy_from_slice(&slice[1..]); a}); //u24
+            let a = u24(slice, 1);
             oper!("OpGlobGet\t<-" "\t#{}", a);
         } 
         FBOpCode::OpGlobClone => {
-            let a = u32::from_le_bytes({let mut a = [0; 4]; a[0..3].copy_from_slice(&slice[1..]); a}); //u24
+            let a = u24(slice, 1);
             oper!("OpGlobClone\t<~" "\t#{}", a);
         } 
         FBOpCode::OpLocSet => {
-            let a = u32::from_le_bytes({let mut a = [0; 4]; a[0..3].copy_from_slice(&slice[1..]); a}); //u24
+            let a = u24(slice, 1);
             oper!("OpLocSet\t->" "\t#{}", a);
         }
         FBOpCode::OpLocGet => {
-            let a = u32::from_le_bytes({let mut a = [0; 4]; a[0..3].copy_from_slice(&slice[1..]); a}); //u24
+            let a = u24(slice, 1);
             oper!("OpLocGet\t<-" "\t#{}", a);
         } 
         FBOpCode::OpLocClone => {
-            let a = u32::from_le_bytes({let mut a = [0; 4]; a[0..3].copy_from_slice(&slice[1..]); a}); //u24
+            let a = u24(slice, 1);
             oper!("OpLocClone\t<~" "\t#{}", a);
         } 
     } 
 }
 
-pub fn run(vm: &mut Vm, size: usize) -> Option<u8> {
+pub fn run(vm: &mut Vm, op: FBOpCode) -> Result<Option<u8>, PhoenixError> {
+    // Operands directly follow the opcode
+    let at = vm.pc as usize + 1;
 
-    let slice = &vm.chunk.code[vm.pc as usize..vm.pc as usize + size];
-
-    match FBOpCode::from(slice[0]) {
-        FBOpCode::OpReturn => return Some(0),
+    match op {
+        FBOpCode::OpReturn => return Ok(Some(0)),
         FBOpCode::OpConstant => {
-            let value = match &vm.chunk.consts.as_vm()[u32::from_le_bytes({let mut a = [0; 4]; a[0..3].copy_from_slice(&slice[1..]); a}) as usize] { //u24
+            let value = match &vm.chunk.consts.as_vm()[u24(&vm.chunk.code, at)] {
                 Const::Int(v) => Value::Int(*v),
-                Const::Dec(v) => Value::Dec(f64::from_bits(*v)),
+                Const::Dec(v) => Value::Dec(*v),
                 Const::String(v) => {
                     let v = vm.strings.intern_str(v);
                     Value::Str(v)
                 },
                 Const::Char(c) => Value::Char(*c),
-            }; vm.stack.push(value) }
-        FBOpCode::OpTrue => vm.stack.push(Value::Bool(true)), FBOpCode::OpFalse => vm.stack.push(Value::Bool(false)),
-        FBOpCode::OpPop => { vm.stack.pop(); }
+                Const::I8(v) => Value::I8(*v), Const::I16(v) => Value::I16(*v), Const::I32(v) => Value::I32(*v), Const::I64(v) => Value::I64(*v),
+                Const::U8(v) => Value::U8(*v), Const::U16(v) => Value::U16(*v), Const::U32(v) => Value::U32(*v), Const::U64(v) => Value::U64(*v),
+                Const::F32(v) => Value::F32(f32::from_bits(*v)), Const::F64(v) => Value::F64(f64::from_bits(*v)),
+            }; vm.stack.push(value)? }
+        FBOpCode::OpTrue => vm.stack.push(Value::Bool(true))?, FBOpCode::OpFalse => vm.stack.push(Value::Bool(false))?,
+        FBOpCode::OpPop => { vm.stack.pop()?; }
+        FBOpCode::OpPopN => for _ in 0..vm.chunk.code[at] { vm.stack.pop()?; }
+        // Drops the values under the top one, like the locals of a block under its result, which may point to them
+        FBOpCode::OpPopUnder => {
+            let top = vm.stack.pop()?.depoint(vm).deupvalue(vm).to_owned();
+            for _ in 0..vm.chunk.code[at] { vm.stack.pop()?; }
+            vm.stack.push(top)?;
+        }
         FBOpCode::OpAdd => {
             let val = {
-                let second = vm.stack.pop();
-                let first = vm.stack.pop().depoint(vm); let first = first.deupvalue(vm);
+                let second = vm.stack.pop()?;
+                let first = vm.stack.pop()?.depoint(vm); let first = first.deupvalue(vm);
                 let second = second.depoint(vm); let second = second.deupvalue(vm);
 
-                match (&**first, &**second) {
-                    (Value::Int(int_f), Value::Int(int_s)) => Value::Int(*int_f + *int_s),
-                    (Value::Dec(dec_f), Value::Dec(dec_s)) => Value::Dec(*dec_f + *dec_s),
+                arithmetic!(&**first, &**second, checked_add, +, "addition overflowed",
                     (Value::Str(str), Value::Str(to_concat)) => {
                         let mut new_str = String::from(&**str); new_str.push_str(&**to_concat);
                         drop(first); drop(second);
-                        let v = vm.strings.intern_str(&*new_str);
-                        Value::Str(v)
-                    }
+                        Value::Str(vm.strings.intern_str(&*new_str))
+                    },
                     (Value::Str(str), Value::Char(to_concat)) => {
                         let mut new_str = String::from(&**str); new_str.push(*to_concat);
                         drop(first); drop(second);
-                        let v = vm.strings.intern_str(&*new_str);
-                        Value::Str(v)
-                    }
-                    (_, _) => unreachable!()
-                }
+                        Value::Str(vm.strings.intern_str(&*new_str))
+                    })
             };
-            vm.stack.push(val);
+            vm.stack.push(val)?;
         }
         FBOpCode::OpSub => {
             let val = {
-                let second = vm.stack.pop();
-                let first = vm.stack.pop().depoint(vm); let first = first.deupvalue(vm);
+                let second = vm.stack.pop()?;
+                let first = vm.stack.pop()?.depoint(vm); let first = first.deupvalue(vm);
                 let second = second.depoint(vm); let second = second.deupvalue(vm);
 
-                match (&**first, &**second) {
-                    (Value::Int(int_f), Value::Int(int_s)) => Value::Int(*int_f - *int_s),
-                    (Value::Dec(dec_f), Value::Dec(dec_s)) => Value::Dec(*dec_f - *dec_s),
-                    (_, _) => unreachable!()
-                }
+                arithmetic!(&**first, &**second, checked_sub, -, "subtraction overflowed")
             };
-            vm.stack.push(val);
+            vm.stack.push(val)?;
         }
         FBOpCode::OpMul => {
             let val = {
-                let second = vm.stack.pop();
-                let first = vm.stack.pop().depoint(vm); let first = first.deupvalue(vm);
+                let second = vm.stack.pop()?;
+                let first = vm.stack.pop()?.depoint(vm); let first = first.deupvalue(vm);
                 let second = second.depoint(vm); let second = second.deupvalue(vm);
 
-                match (&**first, &**second) {
-                    (Value::Int(int_f), Value::Int(int_s)) => Value::Int(*int_f * *int_s),
-                    (Value::Dec(dec_f), Value::Dec(dec_s)) => Value::Dec(*dec_f * *dec_s),
-                    (_, _) => unreachable!()
-                }
+                arithmetic!(&**first, &**second, checked_mul, *, "multiplication overflowed")
             };
-            vm.stack.push(val);
+            vm.stack.push(val)?;
         }
         FBOpCode::OpDiv => {
             let val = {
-                let second = vm.stack.pop();
-                let first = vm.stack.pop().depoint(vm); let first = first.deupvalue(vm);
+                let second = vm.stack.pop()?;
+                let first = vm.stack.pop()?.depoint(vm); let first = first.deupvalue(vm);
                 let second = second.depoint(vm); let second = second.deupvalue(vm);
 
-                match (&**first, &**second) {
-                    (Value::Int(int_f), Value::Int(int_s)) => Value::Int(*int_f / *int_s),
-                    (Value::Dec(dec_f), Value::Dec(dec_s)) => Value::Dec(*dec_f / *dec_s),
-                    (_, _) => unreachable!()
+                arithmetic!(&**first, &**second, checked_div, /, "division overflowed or divided by zero")
+            };
+            vm.stack.push(val)?;
+        }
+        // Operand types were checked by the compiler, only their representation is looked at
+        op @ (FBOpCode::OpAddInt | FBOpCode::OpSubInt | FBOpCode::OpMulInt | FBOpCode::OpDivInt) => {
+            let (second, first) = (operand!(vm, Int), operand!(vm, Int));
+            let val = match op {
+                FBOpCode::OpAddInt => first.checked_add(second).ok_or_else(|| overflow("Int", "addition overflowed"))?,
+                FBOpCode::OpSubInt => first.checked_sub(second).ok_or_else(|| overflow("Int", "subtraction overflowed"))?,
+                FBOpCode::OpMulInt => first.checked_mul(second).ok_or_else(|| overflow("Int", "multiplication overflowed"))?,
+                _ => first.checked_div(second).ok_or_else(|| overflow("Int", "division overflowed or divided by zero"))?,
+            };
+            vm.stack.push(Value::Int(val))?;
+        }
+        op @ (FBOpCode::OpAddDec | FBOpCode::OpSubDec | FBOpCode::OpMulDec | FBOpCode::OpDivDec) => {
+            let (second, first) = (operand!(vm, Dec), operand!(vm, Dec));
+            let val = match op {
+                FBOpCode::OpAddDec => first.checked_add(second).ok_or_else(|| overflow("Dec", "addition overflowed"))?,
+                FBOpCode::OpSubDec => first.checked_sub(second).ok_or_else(|| overflow("Dec", "subtraction overflowed"))?,
+                FBOpCode::OpMulDec => first.checked_mul(second).ok_or_else(|| overflow("Dec", "multiplication overflowed"))?,
+                _ => first.checked_div(second).ok_or_else(|| overflow("Dec", "division overflowed or divided by zero"))?,
+            };
+            vm.stack.push(Value::Dec(val))?;
+        }
+        FBOpCode::OpConcat => {
+            let (second, first) = (operand!(vm, Str), operand!(vm, Str));
+            let mut new_str = String::from(&*first); new_str.push_str(&second);
+            let val = Value::Str(vm.strings.intern_str(&new_str));
+            vm.stack.push(val)?;
+        }
+        FBOpCode::OpConcatChar => {
+            let (second, first) = (operand!(vm, Char), operand!(vm, Str));
+            let mut new_str = String::from(&*first); new_str.push(second);
+            let val = Value::Str(vm.strings.intern_str(&new_str));
+            vm.stack.push(val)?;
+        }
+        op @ (FBOpCode::OpWrapAdd | FBOpCode::OpWrapSub | FBOpCode::OpWrapMul | FBOpCode::OpSatAdd | FBOpCode::OpSatSub | FBOpCode::OpSatMul) => {
+            let val = {
+                let second = vm.stack.pop()?;
+                let first = vm.stack.pop()?.depoint(vm); let first = first.deupvalue(vm);
+                let second = second.depoint(vm); let second = second.deupvalue(vm);
+
+                match op {
+                    FBOpCode::OpWrapAdd => int_arithmetic!(&**first, &**second, wrapping_add),
+                    FBOpCode::OpWrapSub => int_arithmetic!(&**first, &**second, wrapping_sub),
+                    FBOpCode::OpWrapMul => int_arithmetic!(&**first, &**second, wrapping_mul),
+                    FBOpCode::OpSatAdd => int_arithmetic!(&**first, &**second, saturating_add),
+                    FBOpCode::OpSatSub => int_arithmetic!(&**first, &**second, saturating_sub),
+                    _ => int_arithmetic!(&**first, &**second, saturating_mul),
                 }
             };
-            vm.stack.push(val);
+            vm.stack.push(val)?;
         }
         FBOpCode::OpNeg => {
             let val = {
-                let value = vm.stack.pop().depoint(vm); let value = value.deupvalue(vm);
+                let value = vm.stack.pop()?.depoint(vm); let value = value.deupvalue(vm);
 
                 match &**value {
-                    Value::Int(value) => Value::Int(-*value),
-                    Value::Dec(value) => Value::Dec(-*value),
+                    Value::Int(value) => Value::Int(value.checked_neg().ok_or_else(|| overflow("Int", "negation overflowed"))?),
+                    Value::Dec(value) => Value::Dec(value.checked_neg().ok_or_else(|| overflow("Dec", "negation overflowed"))?),
+                    Value::F32(value) => Value::F32(-*value), Value::F64(value) => Value::F64(-*value),
+                    Value::I8(value) => Value::I8(value.checked_neg().ok_or_else(|| overflow("I8", "negation overflowed"))?),
+                    Value::I16(value) => Value::I16(value.checked_neg().ok_or_else(|| overflow("I16", "negation overflowed"))?),
+                    Value::I32(value) => Value::I32(value.checked_neg().ok_or_else(|| overflow("I32", "negation overflowed"))?),
+                    Value::I64(value) => Value::I64(value.checked_neg().ok_or_else(|| overflow("I64", "negation overflowed"))?),
+                    Value::U8(value) => Value::U8(value.checked_neg().ok_or_else(|| overflow("U8", "negation overflowed"))?),
+                    Value::U16(value) => Value::U16(value.checked_neg().ok_or_else(|| overflow("U16", "negation overflowed"))?),
+                    Value::U32(value) => Value::U32(value.checked_neg().ok_or_else(|| overflow("U32", "negation overflowed"))?),
+                    Value::U64(value) => Value::U64(value.checked_neg().ok_or_else(|| overflow("U64", "negation overflowed"))?),
                     _ => unreachable!(),
                 }
             };
-            vm.stack.push(val);
+            vm.stack.push(val)?;
+        }
+        FBOpCode::OpPrint => { print!("{}", vm.stack.pop()?.depoint(vm).display(&vm.heap)) }
+        FBOpCode::OpCast => {
+            let to = CastTy::from(vm.chunk.code[at]);
+            let value = vm.stack.pop()?.depoint(vm).deupvalue(vm).to_owned();
+            let value = vm.cast(value, to)?;
+            vm.stack.push(value)?;
+        }
+        FBOpCode::OpFormat => {
+            let parts = (0..vm.chunk.code[at]).map(|_| vm.stack.pop()).collect::<Result<Vec<Value>, _>>()?;
+            let mut str = String::new();
+            for part in parts.into_iter().rev() { write!(str, "{}", part.depoint(vm).display(&vm.heap)).unwrap() }
+            let str = vm.strings.intern_str(&str);
+            vm.stack.push(Value::Str(str))?;
         }
-        FBOpCode::OpPrint => { print!("{}", &**vm.stack.pop().depoint(vm).deupvalue(vm)) }
         FBOpCode::OpGlobSet => {
-            let name = &vm.chunk.consts.as_vm()[u32::from_le_bytes({let mut a = [0; 4]; a[0..3].copy_from_slice(&slice[1..]); a}) as usize]; //u24
+            let name = &vm.chunk.consts.as_vm()[u24(&vm.chunk.code, at)];
             let name = if let Const::String(str) = name { str } else { unreachable!() };
             let name = vm.strings.intern_str(name);
-            let value = vm.stack.pop().depoint(vm).deupvalue(vm).to_owned();
+            let value = vm.stack.pop()?.depoint(vm).deupvalue(vm).to_owned();
 
             match vm.globals.get_mut(&name) {
-                Some(Value::Upv(rc)) => *rc.borrow_mut() = value,
+                Some(Value::Upv(gc)) => *vm.heap.get_mut(*gc) = value,
                 Some(glob) => *glob = value,
                 None => { vm.globals.insert(name, value); }
             };
         }
         s @ (FBOpCode::OpGlobGet | FBOpCode::OpGlobClone) => {
-            let name = &vm.chunk.consts.as_vm()[u32::from_le_bytes({let mut a = [0; 4]; a[0..3].copy_from_slice(&slice[1..]); a}) as usize]; //u24
+            let name = &vm.chunk.consts.as_vm()[u24(&vm.chunk.code, at)];
             let name = if let Const::String(str) = name { str } else { unreachable!() }; let name = vm.strings.intern_str(name);
 
             let value = if s == FBOpCode::OpGlobGet {Value::Ptr(Pointer::Global(name))} else {vm.globals[&name].deupvalue(vm).clone()};
-            vm.stack.push(value);
+            vm.stack.push(value)?;
         }
         FBOpCode::OpLocSet => {
-            let addr = u32::from_le_bytes({let mut a = [0; 4]; a[0..3].copy_from_slice(&slice[1..]); a}) as usize; //u24
-            let value = vm.stack.pop().depoint(vm).deupvalue(vm).to_owned();
+            let addr = u24(&vm.chunk.code, at);
+            let value = vm.stack.pop()?.depoint(vm).deupvalue(vm).to_owned();
 
             match &mut vm.stack[addr] {
-                Value::Upv(rc) => *rc.borrow_mut() = value,
+                Value::Upv(gc) => { let gc = *gc; *vm.heap.get_mut(gc) = value }
                 _ => vm.with_depnt_upved(addr, |mut val| **val = value)
             };
         }
         s @ (FBOpCode::OpLocGet | FBOpCode::OpLocClone) => {
-            let addr = u32::from_le_bytes({let mut a = [0; 4]; a[0..3].copy_from_slice(&slice[1..]); a}) as usize; //u24
+            let addr = u24(&vm.chunk.code, at);
             let value = if s == FBOpCode::OpLocGet {vm.denested_pointer(addr)} else {vm.with_depnt_upved(addr, |val| val.clone())};
-            vm.stack.push(value);
+            vm.stack.push(value)?;
         }
     }
-    None
+    Ok(None)
 }
 
 
@@ -13,10 +13,20 @@ mod vm;
 mod compiler;
 mod debug;
 mod utils;
+mod decimal;
 pub mod error;
+pub mod source;
+/// Sources of the programs of the `vm` benchmark, their results are checked by the tests
+#[cfg(test)]
+#[path = "../benches/phx/mod.rs"]
+mod bench_programs;
 
-const IDENTIFIER_MAX_LENGTH: usize = 32;
-const STACK_LENGTH: usize = 512;
+pub use compiler::{Compiler, Profile};
+pub use vm::StackConfig;
+
+/// Runs a compiled program to its end, the exit code is the one it returns with.
+/// With `debug` every executed instruction is printed.
+pub fn run(chunk: Chunk, stack: StackConfig, debug: bool) -> Result<u8, error::PhoenixError> { vm::Vm::new(chunk).with_stack(stack).run(debug) }
 
 //#[cfg(test)]
 mod test {
@@ -26,16 +36,516 @@ mod test {
     use clap::error::ErrorKind;
     use toml::Table;
 
-    use crate::{compiler::Compiler, debug::debug_chunk, error::PhoenixError, vm::{Vm, Stack, value::{Value, Pointer}}, strings::InternStr};
+    use std::sync::{Arc, Mutex, mpsc};
+
+    use crate::{error::CompErrID, compiler::{Compiler, Profile, peephole, chunk::Chunk, scanner::{Scanner, Indent}, module::{Module, ast}, token::{Token, TokenType, Trivia, TriviaKind}}, debug::debug_chunk, error::PhoenixError, vm::{Vm, Stack, value::{Value, Pointer}}, strings::{InternStr, InternStrSync}, source::{SourceMap, FileId, Span}};
+
+    fn compile_src(src: &str) -> Result<Chunk, Vec<PhoenixError>> {
+        let mut sources = SourceMap::default();
+        let file = sources.add("test.phx", src);
+        let (tokens, symbols, mut errors) = Scanner::new(src.to_owned(), file).scan();
+        let compiler = Arc::new(Mutex::new(Compiler::new(InternStrSync::new())));
+        let mut module = Module::new(tokens, symbols, Arc::from("test"), sources.get(file).clone(), compiler);
+        let (tx, _rx) = mpsc::channel();
+        if let Err(parse_errors) = module.compile(tx) { errors.extend(parse_errors) }
+        if !errors.is_empty() { return Err(errors) }
+        Ok(module.chunk.take().unwrap().build())
+    }
+
+    fn run_src(src: &str) -> Result<Vm, PhoenixError> {
+        let chunk = compile_src(src).map_err(|mut errs| errs.remove(0))?;
+        let mut vm = Vm::new(chunk);
+        vm.run(false)?;
+        Ok(vm)
+    }
 
     #[test]
     pub fn test() -> Result<(), Vec<PhoenixError>> {
-        let chunk = Compiler::compile(PathBuf::from(r"/home/matteo/rust/phoenixlang/test/"))?;
+        let chunk = Compiler::compile(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test"), Profile::Debug, &mut SourceMap::default())?;
         debug_chunk(&chunk);
-        let vm = Vm { chunk, pc: 0, stack: Stack::new(), globals: Default::default(), strings: InternStr::new() }.run(false);
+        let vm = Vm::new(chunk).run(false);
         Ok(())
     }
 
+    #[test]
+    pub fn casts() {
+        let vm = run_src("let a = 7 as Dec / 2 as Dec\nlet b = 3.9 as Int\nlet c = 97 as Char\nlet d = \"42\" as Int + 1\nlet e = 1.5 as Str + 'x'").unwrap();
+        assert!(matches!(vm.stack[0], Value::Dec(d) if d.to_string() == "3.5"));
+        assert!(matches!(vm.stack[1], Value::Int(3)));
+        assert!(matches!(vm.stack[2], Value::Char('a')));
+        assert!(matches!(vm.stack[3], Value::Int(43)));
+        assert!(matches!(&vm.stack[4], Value::Str(str) if &**str == "1.5x"));
+
+        assert!(matches!(run_src("let a = \"4x\" as Int"), Err(PhoenixError::Runtime(_))));
+        assert!(matches!(run_src("let a = \"1e300\" as Dec as Int"), Err(PhoenixError::Runtime(_))));
+        assert!(matches!(compile_src("let a = 'a' as Dec").err().as_deref(), Some([PhoenixError::Compile { id: CompErrID::TypeError, .. }])));
+
+        // Casting a variable leaves its type alone
+        let vm = run_src("let x = 5\nlet y = x as Dec\nlet z = x as Char").unwrap();
+        assert!(matches!(vm.stack[0], Value::Int(5)));
+        assert!(matches!(vm.stack[1], Value::Dec(d) if d.to_string() == "5.0"));
+        assert!(matches!(vm.stack[2], Value::Char('\u{5}')));
+        let vm = run_src("let x = 5\nlet y = x as U8\nlet z = x + 300").unwrap();
+        assert!(matches!(vm.stack[1], Value::U8(5)));
+        assert!(matches!(vm.stack[2], Value::Int(305)));
+    }
+
+    #[test]
+    pub fn aliases() {
+        let vm = run_src("alias UserId = Int\nalias Id = UserId\nlet a: Id = 3\nlet b = 2.0 as UserId").unwrap();
+        assert!(matches!(vm.stack[0], Value::Int(3)));
+        assert!(matches!(vm.stack[1], Value::Int(2)));
+
+        let errs = compile_src("alias UserId = Int\nlet a: UserId = 'c'").err().unwrap();
+        assert!(matches!(&errs[..], [PhoenixError::Compile { id: CompErrID::TypeError, msg, .. }] if msg.contains("UserId (alias of Int)")));
+        assert!(matches!(compile_src("alias Int = Dec").err().as_deref(), Some([PhoenixError::Compile { id: CompErrID::DuplicateSymbol, .. }])));
+        // Nothing can import an alias yet, so `pub` is refused rather than ignored
+        assert!(matches!(compile_src("pub alias UserId = Int\nlet a = 1").err().as_deref(), Some([PhoenixError::Compile { id: CompErrID::UnexpectedToken, msg, .. }]) if msg.contains("'pub'")));
+    }
+
+    #[test]
+    pub fn inference() {
+        let vm = run_src("let a: Dec = 1\nlet b = 2\nlet c = 1 + 2.5\nlet d: Dec = b * 2").unwrap();
+        assert!(matches!(vm.stack[0], Value::Dec(d) if d.to_string() == "1.0"));
+        assert!(matches!(vm.stack[1], Value::Dec(d) if d.to_string() == "2.0"));
+        assert!(matches!(vm.stack[2], Value::Dec(d) if d.to_string() == "3.5"));
+        assert!(matches!(vm.stack[3], Value::Dec(d) if d.to_string() == "4.0"));
+
+        // A cast does not decide the type of the variable, the later use does
+        let vm = run_src("let x = 5\nlet y = x as Dec\nlet z: U8 = x").unwrap();
+        assert!(matches!(vm.stack[0], Value::U8(5)));
+        assert!(matches!(vm.stack[1], Value::Dec(d) if d.to_string() == "5.0"));
+        assert!(matches!(compile_src("let x = 5\nlet y = x as Bool").err().as_deref(), Some([PhoenixError::Compile { id: CompErrID::TypeError, msg, .. }]) if msg.contains("'Int' cannot be cast")));
+
+        let errs = compile_src("let a: Int = 1.5").err().unwrap();
+        assert!(matches!(&errs[..], [PhoenixError::Compile { id: CompErrID::TypeError, msg, .. }] if msg.contains("'{decimal}'")));
+    }
+
+    #[test]
+    pub fn typed_ast() {
+        let src = "alias Byte = U8\nlet a = 1\nlet b: Byte = a + 2\nprint \"{b}!\"\nb += 1\n'c' as U32";
+        let mut sources = SourceMap::default();
+        let file = sources.add("test.phx", src);
+        let (tokens, symbols, errors) = Scanner::new(src.to_owned(), file).scan();
+        assert!(errors.is_empty());
+        let compiler = Arc::new(Mutex::new(Compiler::new(InternStrSync::new())));
+        let mut module = Module::new(tokens, symbols, Arc::from("test"), sources.get(file).clone(), compiler);
+        let dump = ast::dump(&module.typed_ast().map_err(|errs| errs.len()).unwrap());
+
+        // The literal of `a` only gets its type from the later use
+        assert_eq!(dump, "Alias Byte = U8\nLet a Local(0) : Void\n  Number 1 : U8\nLet b Local(1) : Void\n  Binary + : U8\n    Var a Local(0) : U8\n    Number 2 : U8\n\
+            Print\n  Interp : Str\n    Var b Local(1) : U8\n    Str \"!\" : Str\nAssign b += Local(1) : Void\n  Number 1 : U8\nCast U32 : U32\n  Char 'c' : Char\n");
+    }
+
+    #[test]
+    pub fn sized_numbers() {
+        let vm = run_src("let a: U8 = 200\nlet b = a + 55\nlet c = 1.5f32 * 2\nlet d = 'a' as U16 as I64").unwrap();
+        assert!(matches!(vm.stack[1], Value::U8(255)));
+        assert!(matches!(vm.stack[2], Value::F32(f) if f == 3.0));
+        assert!(matches!(vm.stack[3], Value::I64(97)));
+        assert!(matches!(run_src("let x = 255u8\nlet a = x + 1"), Err(PhoenixError::Runtime(_))));
+        assert!(matches!(run_src("let a = 300u16 as U8"), Err(PhoenixError::Runtime(_))));
+
+        assert!(matches!(compile_src("let a: I8 = 128").err().as_deref(), Some([PhoenixError::Compile { id: CompErrID::TypeError, .. }])));
+        assert!(matches!(compile_src("let a = 1u8 + 1i8").err().as_deref(), Some([PhoenixError::Compile { id: CompErrID::TypeError, .. }])));
+
+        // Integer literals become floats only when no precision is lost
+        assert!(matches!(run_src("let a: F32 = 16777216\nlet b: F64 = 9007199254740992").unwrap().stack[0], Value::F32(f) if f == 16777216.0));
+        assert!(matches!(compile_src("let a: F32 = 16777217").err().as_deref(), Some([PhoenixError::Compile { id: CompErrID::TypeError, msg, .. }]) if msg.contains("'F32'")));
+        assert!(compile_src("let a: F64 = 9007199254740993").is_err());
+
+        // Unsigned integers have no negation, even when the type is only decided later
+        let negate_err = |src| matches!(compile_src(src).err().as_deref(), Some([PhoenixError::Compile { id: CompErrID::TypeError, msg, .. }]) if msg.contains("'negate'"));
+        assert!(negate_err("let a: U8 = 1\nlet b = -a"));
+        assert!(negate_err("let a = 1\nlet b = -a\nlet c: U32 = a"));
+        assert!(matches!(run_src("let a = 1\nlet b = -a\nlet c: I8 = a").unwrap().stack[1], Value::I8(-1)));
+    }
+
+    #[test]
+    pub fn decimals() {
+        let vm = run_src("let a = 0.1 + 0.2\nlet b = 1 / 3.0\nlet c = 2.5 * 0.10\nlet d = 0.1f64 + 0.2f64\nlet e = 0.125 as F64 as Dec").unwrap();
+        assert!(matches!(vm.stack[0], Value::Dec(d) if d.to_string() == "0.3"));
+        assert!(matches!(vm.stack[1], Value::Dec(d) if d.to_string() == "0.3333333333333333333333333333"));
+        assert!(matches!(vm.stack[2], Value::Dec(d) if d.to_string() == "0.250"));
+        assert!(matches!(vm.stack[3], Value::F64(f) if f != 0.3));
+        assert!(matches!(vm.stack[4], Value::Dec(d) if d.to_string() == "0.125"));
+
+        // Products of high scale values are rounded instead of overflowing, at runtime as when folded
+        let vm = run_src("let a = 1 / 3.0\nlet b = a * a\nconst A: Dec = 1 / 3.0\nlet c = A * -A\nlet d = (1/3.0)*(1/3.0)").unwrap();
+        assert!(matches!(vm.stack[1], Value::Dec(d) if d.to_string() == "0.1111111111111111111111111111"));
+        assert!(matches!(vm.stack[2], Value::Dec(d) if d.to_string() == "-0.1111111111111111111111111111"));
+        assert!(matches!(vm.stack[3], Value::Dec(d) if d.to_string() == "0.1111111111111111111111111111"));
+        assert!(matches!(run_src("let a = 100000000000000000000.0\nlet b = a * a"), Err(PhoenixError::Runtime(_))));
+
+        assert!(matches!(run_src("let z = 0.0\nlet a = 2 / z"), Err(PhoenixError::Runtime(_))));
+    }
+
+    #[test]
+    pub fn overflow() {
+        let vm = run_src("let a = 9223372036854775807 +% 1\nlet b = 250u8 +| 10\nlet c = -128 as I8\nlet d = 0u8 -% 1\nlet e = -9223372036854775808").unwrap();
+        assert!(matches!(vm.stack[0], Value::Int(i64::MIN)));
+        assert!(matches!(vm.stack[1], Value::U8(255)));
+        assert!(matches!(vm.stack[2], Value::I8(-128)));
+        assert!(matches!(vm.stack[3], Value::U8(255)));
+        assert!(matches!(vm.stack[4], Value::Int(i64::MIN)));
+
+        assert!(matches!(run_src("let x = 9223372036854775807\nlet a = x + 1"), Err(PhoenixError::Runtime(_))));
+        assert!(matches!(run_src("let x = 0\nlet a = 1 / x"), Err(PhoenixError::Runtime(_))));
+        assert!(matches!(compile_src("let a = 99999999999999999999999999999999999999999999").err().as_deref(), Some([PhoenixError::Compile { .. }])));
+        assert!(matches!(compile_src("let a = 1.5 +% 1").err().as_deref(), Some([PhoenixError::Compile { id: CompErrID::TypeError, .. }])));
+    }
+
+    #[test]
+    pub fn constant_folding() {
+        let chunk = compile_src("const BASE: U8 = 200\nconst NAME: Str = \"n\" + '!'\nlet a = 1 + 2 * 3\nlet b = BASE +% 100\nlet c = \"{NAME}:{BASE}\"\nlet d = -(4.5 / 2)").unwrap();
+        // Every value is a single constant, no arithmetic is left for the VM
+        assert_eq!(chunk.code.len(), 4 * 4);
+        assert!(chunk.code.chunks(4).all(|op| op[0] == crate::FBOpCode::OpConstant as u8));
+
+        let vm = run_src("const BASE: U8 = 200\nconst NAME: Str = \"n\" + '!'\nlet a = 1 + 2 * 3\nlet b = BASE +% 100\nlet c = \"{NAME}:{BASE}\"\nlet d = -(4.5 / 2)").unwrap();
+        assert!(matches!(vm.stack[0], Value::Int(7)));
+        assert!(matches!(vm.stack[1], Value::U8(44)));
+        assert!(matches!(&vm.stack[2], Value::Str(s) if &**s == "n!:200"));
+        assert!(matches!(vm.stack[3], Value::Dec(d) if d.to_string() == "-2.25"));
+
+        let errs = compile_src("const A: U8 = 200 + 100\nlet b = 1 / 0\nlet c = 0\nconst D: Int = c\nconst E: Int = 1\nE = 2\nconst E: Int = 3").err().unwrap();
+        assert!(matches!(&errs[..], [
+            PhoenixError::Compile { id: CompErrID::ConstantOverflow, .. }, PhoenixError::Compile { id: CompErrID::NotConstant, .. },
+            PhoenixError::Compile { id: CompErrID::InvalidSymbol, .. }, PhoenixError::Compile { id: CompErrID::DuplicateSymbol, .. },
+            PhoenixError::Compile { id: CompErrID::DivisionByZero, .. }]));
+    }
+
+    #[test]
+    pub fn peephole() {
+        let src = "let a = 1\na\nlet c = { let d = a + 1; let e = a + 2; d }\n{ let f = a + 1; let g = a + 2; let h = a + 3; }\nprint \"{c}\"";
+        let plain = compile_src(src).unwrap();
+        let mut optimized = compile_src(src).unwrap();
+        peephole::optimize(&mut optimized);
+        use crate::FBOpCode::*;
+        // `a` alone is never pushed, the pops of the locals of the void block are counted
+        assert_eq!(optimized.code.len(), plain.code.len() - 5 - 3 + 2);
+        assert!(optimized.code.windows(2).any(|ops| ops == [OpPopN as u8, 3]));
+
+        let run = |chunk| { let mut vm = Vm::new(chunk); vm.run(false).map(|_| vm) };
+        let (plain, optimized) = (run(plain).unwrap(), run(optimized).unwrap());
+        assert_eq!(plain.stack.iter().take(4).map(ToString::to_string).collect::<Vec<_>>(), optimized.stack.iter().take(4).map(ToString::to_string).collect::<Vec<_>>());
+
+        let mut empty = compile_src("1; 2").unwrap();
+        peephole::optimize(&mut empty);
+        assert!(empty.code.is_empty() && run(empty).is_ok());
+    }
+
+    #[test]
+    pub fn specialized_ops() {
+        use crate::FBOpCode::*;
+        let src = "let a = 6\nlet b = a * a - a / 2 + 1\nlet c = 0.5\nlet d = c * 3 + c\nlet s = \"ab\"\nlet t = s + s + '!'\nt += 'c'\nlet u = 2u8\nlet v = u + u";
+        let ops: Vec<_> = {
+            let code = compile_src(src).unwrap().code;
+            let mut i = 0;
+            std::iter::from_fn(|| { let op = *code.get(i)?; i += crate::FBOpCode::size()[op as usize] as usize; Some(op) }).collect()
+        };
+        for op in [OpMulInt, OpSubInt, OpDivInt, OpAddInt, OpMulDec, OpAddDec, OpConcat, OpConcatChar] { assert!(ops.contains(&(op as u8))) }
+        // Sized integers still take the generic path
+        assert_eq!(ops.iter().filter(|op| **op == OpAdd as u8).count(), 1);
+
+        let vm = run_src(src).unwrap();
+        assert!(matches!(vm.stack[1], Value::Int(34)));
+        assert!(matches!(vm.stack[3], Value::Dec(d) if d.to_string() == "2.0"));
+        assert!(matches!(&vm.stack[5], Value::Str(s) if &**s == "abab!c"));
+        assert!(matches!(vm.stack[7], Value::U8(4)));
+    }
+
+    #[test]
+    pub fn value_layout() {
+        // The variant is kept in the unused scales of a Dec
+        assert_eq!(size_of::<Value>(), 24);
+        let vm = run_src("let a = 1.25\nlet b = a * a\nlet c = 7\nlet d = c + c\nlet e = true").unwrap();
+        assert!(matches!(vm.stack[1], Value::Dec(d) if d.to_string() == "1.5625"));
+        assert!(matches!(vm.stack[3], Value::Int(14)));
+        assert!(matches!(vm.stack[4], Value::Bool(true)));
+    }
+
+    #[test]
+    pub fn stack() {
+        use crate::StackConfig;
+        let src = "let a = 1\nlet b = 2\nlet c = 3\nlet d = 4\nlet e = a + b + c + d";
+        let run = |size, max| { let mut vm = Vm::new(compile_src(src).unwrap()).with_stack(StackConfig { size, max }); vm.run(false).map(|_| vm) };
+
+        // Growing keeps the addresses of locals
+        assert!(matches!(run(1, 8).unwrap().stack[4], Value::Int(10)));
+        let overflow = run(4, 4).err().unwrap();
+        assert!(matches!(overflow, PhoenixError::StackOverflow { depth: 1, size: 4 }));
+        assert_eq!(format!("{overflow:?}"), "Program panicked! Stack overflow at call depth 1, the stack holds at most 4 values");
+
+        let mut vm = Vm::new(compile_src("1").unwrap());
+        vm.chunk.code = vec![crate::FBOpCode::OpPop as u8];
+        assert!(matches!(vm.run(false), Err(PhoenixError::Runtime(msg)) if msg.contains("underflow")));
+
+        assert_eq!(StackConfig::default().with_sizes(Some(1 << 20), None), Ok(StackConfig { size: 1 << 20, max: 1 << 20 }));
+        assert!(StackConfig::default().with_sizes(Some(8), Some(4)).is_err());
+        assert_eq!(Compiler::stack_config(&PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test")).map_err(|errs| errs.len()), Ok(StackConfig::default()));
+    }
+
+    #[test]
+    pub fn garbage_collection() {
+        use crate::vm::heap::Object;
+        let mut vm = run_src("let a = 1\nlet b = 2").unwrap();
+        Value::Ptr(Pointer::Local(0)).promote_upv(&mut vm);
+        // Two cycles, only the second one is reachable
+        let cycle = |vm: &mut Vm| {
+            let first = vm.heap.alloc(Object::Upv(Value::Int(0)));
+            let second = vm.heap.alloc(Object::Upv(Value::Upv(first)));
+            *vm.heap.get_mut(first) = Value::Upv(second);
+            first
+        };
+        cycle(&mut vm);
+        vm.stack[1] = Value::Upv(cycle(&mut vm));
+
+        vm.collect_garbage();
+        let stats = vm.gc_stats();
+        assert_eq!((stats.collections, stats.freed_objects), (1, 2));
+        assert!(matches!(**Value::Ptr(Pointer::Local(0)).depoint(&vm).deupvalue(&vm), Value::Int(1)));
+        assert!(matches!(vm.heap.get(match vm.stack[1] { Value::Upv(gc) => gc, _ => unreachable!() }), Value::Upv(_)));
+
+        // A promoted variable is printed and interpolated as the value it shares
+        let mut vm = run_src("let a = 7").unwrap();
+        Value::Ptr(Pointer::Local(0)).promote_upv(&mut vm);
+        // Execution resumes right after the `let a` that already ran
+        vm.chunk = compile_src("let a = 7\nlet b = \"{a}!\"\nlet c = a as Str").unwrap();
+        assert_eq!(vm.pc, 4);
+        vm.run(false).unwrap();
+        assert!(matches!(&vm.stack[1], Value::Str(str) if &**str == "7!"));
+        assert!(matches!(&vm.stack[2], Value::Str(str) if &**str == "7"));
+        assert_eq!(vm.stack[0].display(&vm.heap).to_string(), "7");
+
+        // Only the strings left on the stack survive
+        let mut vm = run_src("let a = \"x\"\nlet b = a + \"y\"\nb = a + \"z\"").unwrap();
+        vm.collect_garbage();
+        let stats = vm.gc_stats();
+        assert_eq!((stats.freed_strings, stats.heap_size), (3, 3));
+        assert!(matches!(&vm.stack[1], Value::Str(str) if &**str == "xz"));
+        assert!(!vm.heap.should_collect(vm.strings.allocated()));
+
+        // Surviving strings push the next collection back instead of triggering one after every instruction
+        let vm = run_src(&format!("let s = \"a\"\n{}{}", "s = s + s\n".repeat(21), "let n = 1\n".repeat(200))).unwrap();
+        let stats = vm.gc_stats();
+        assert!((1..=3).contains(&stats.collections) && stats.heap_size > 1 << 21);
+        assert!(!vm.heap.should_collect(vm.strings.allocated()));
+    }
+
+    #[test]
+    pub fn bench_programs() {
+        use crate::bench_programs as phx;
+        // The benchmarked programs run the same whether optimized or not
+        for (name, src) in phx::programs() {
+            let path = phx::write_project(&std::env::temp_dir().join("phoenix-bench-programs"), name, &src);
+            let run = |profile| {
+                let mut vm = Vm::new(Compiler::compile(path.clone(), profile, &mut SourceMap::default()).unwrap());
+                vm.run(false).unwrap();
+                vm.stack.iter().take(3).map(ToString::to_string).collect::<Vec<_>>()
+            };
+            let debug = run(Profile::Debug);
+            assert_eq!(debug, run(Profile::Release), "{name}");
+            if name == "fibonacci" { assert_eq!(debug[2], "-32315797198045260") }
+        }
+    }
+
+    #[test]
+    pub fn numeric_literals() {
+        let vm = run_src("let a = 0xFF + 0b1010 + 0o17\nlet b = 1_000_000\nlet c = 6.02e23\nlet d = 1.5e-3\nlet e = 0xFFu8\nlet f = 1e300f64\nlet g = -0x80i8").unwrap();
+        assert!(matches!(vm.stack[0], Value::Int(280)));
+        assert!(matches!(vm.stack[1], Value::Int(1_000_000)));
+        assert!(matches!(vm.stack[2], Value::Dec(d) if d.to_string() == "602000000000000000000000.0"));
+        assert!(matches!(vm.stack[3], Value::Dec(d) if d.to_string() == "0.0015"));
+        assert!(matches!(vm.stack[4], Value::U8(255)));
+        assert!(matches!(vm.stack[5], Value::F64(f) if f == 1e300));
+        assert!(matches!(vm.stack[6], Value::I8(-128)));
+
+        assert!(matches!(compile_src("let a = 0b102").err().as_deref(), Some([PhoenixError::Compile { id: CompErrID::InvalidNumber, span: Span { start: 12, end: 13, .. }, .. }])));
+        assert!(matches!(compile_src("let a = 0x").err().as_deref(), Some([PhoenixError::Compile { id: CompErrID::InvalidNumber, .. }])));
+        assert!(matches!(compile_src("let a = 1e+").err().as_deref(), Some([PhoenixError::Compile { id: CompErrID::InvalidNumber, .. }])));
+        assert!(matches!(compile_src("let a = 0x1_0000_0000_0000_0000_0000_0000_0000_0000_0000").err().as_deref(), Some([PhoenixError::Compile { id: CompErrID::InvalidNumber, .. }])));
+    }
+
+    #[test]
+    pub fn strings() {
+        let vm = run_src(r##"let a = "\u{1F600}\x41\{\""
+let b = r"C:\new\{x}"
+let c = r#"say "hi""#
+let n = 3
+let d = "n = {n}, n + 1.5 = {n + 1.5}, {"ok"}!""##).unwrap();
+        assert!(matches!(&vm.stack[0], Value::Str(str) if &**str == "\u{1F600}A{\""));
+        assert!(matches!(&vm.stack[1], Value::Str(str) if &**str == r"C:\new\{x}"));
+        assert!(matches!(&vm.stack[2], Value::Str(str) if &**str == r#"say "hi""#));
+        assert!(matches!(&vm.stack[4], Value::Str(str) if &**str == "n = 3.0, n + 1.5 = 4.5, ok!"));
+
+        assert!(matches!(compile_src(r#"let a = "\q""#).err().as_deref(), Some([PhoenixError::Compile { id: CompErrID::InvalidEscape, .. }])));
+        assert!(matches!(compile_src(r#"let a = "\u{110000}""#).err().as_deref(), Some([PhoenixError::Compile { id: CompErrID::InvalidEscape, .. }])));
+        assert!(matches!(compile_src(r#"let a = "{}""#).err().as_deref(), Some([PhoenixError::Compile { .. }])));
+    }
+
+    #[test]
+    pub fn unicode_identifiers() {
+        // "café" spelled with a precomposed and with a combining accent is the same variable
+        let vm = run_src("let caf\u{e9} = 1\nlet \u{5909}\u{6570} = cafe\u{301} + 1\nlet _\u{3b1}\u{3b2}2 = \u{5909}\u{6570} + 1").unwrap();
+        assert!(matches!(vm.stack[2], Value::Int(3)));
+
+        let long = "a".repeat(100);
+        assert!(run_src(&format!("let {long} = 1")).is_ok());
+        let (_, _, errors) = Scanner::new(format!("let {long} = 1"), FileId(0)).with_max_identifier_length(Some(32)).scan();
+        assert!(matches!(&errors[..], [PhoenixError::Compile { id: CompErrID::IdentifierTooLong, .. }]));
+        assert!(matches!(compile_src("let a\u{2603} = 1").err().as_deref(), Some([PhoenixError::Compile { id: CompErrID::InvalidCharacter, .. }])));
+        assert!(matches!(compile_src("let a\u{bd} = 1\nprint a\u{bd}").err().as_deref(), Some([PhoenixError::Compile { id: CompErrID::InvalidCharacter, .. }, ..])));
+    }
+
+    #[test]
+    pub fn spans() {
+        let src = "let a: Int = 1\na = \"s\" + 'c'";
+        let errs = compile_src(src).err().unwrap();
+        let mut sources = SourceMap::default();
+        sources.add("test.phx", src);
+        assert_eq!(errs[0].render(&sources), "error[TypeError]: Cannot assign expression of type 'Str' to symbol 'a' of type 'Int'\n --> test.phx:2:5\n  |\n2 | a = \"s\" + 'c'\n  |     ^^^^^^^^^");
+    }
+
+    #[test]
+    pub fn tokens() {
+        assert!(size_of::<Token>() <= 24);
+        let (tokens, symbols, _) = Scanner::new("let count2 = count2 + Count".to_owned(), FileId(0)).scan();
+        let [_, a, _, b, _, c, _] = &tokens[..] else { panic!("{tokens:?}") };
+        assert_eq!(a.sym, b.sym);
+        assert!(!a.can_be_type && c.can_be_type);
+        assert_eq!(&**symbols.get(c.sym.unwrap()), "Count");
+        assert_eq!((c.span.start, c.span.end), (22, 27));
+    }
+
+    #[test]
+    pub fn lossless() {
+        let src = "// header\nlet a = 1  // trailing\n\n{\n    let b = \"x { a + 1 } y\"\n\n    b\n}\n";
+        let (tokens, _, trivia, errors) = Scanner::new(src.to_owned(), FileId(0)).scan_lossless();
+        assert!(errors.is_empty());
+        let mut rebuilt = String::new();
+        for (i, token) in tokens.iter().enumerate() {
+            trivia.leading(i).iter().for_each(|piece| rebuilt.push_str(&src[piece.span.start as usize..piece.span.end as usize]));
+            rebuilt.push_str(&src[token.span.start as usize..token.span.end as usize]);
+        }
+        assert_eq!(rebuilt, src);
+        assert!(matches!(trivia.leading(0), [Trivia { kind: TriviaKind::LineComment, .. }, Trivia { kind: TriviaKind::Newline, .. }]));
+
+        let (plain, _, _) = Scanner::new(src.to_owned(), FileId(0)).scan();
+        assert_eq!(plain.len(), tokens.len());
+    }
+
+    #[test]
+    pub fn indentation() {
+        let scan = |src: &str, indent| {
+            let (tokens, _, errors) = Scanner::new(src.to_owned(), FileId(0)).with_indent(indent).scan();
+            (tokens.iter().map(|t| t.ty).collect::<Vec<_>>(), errors)
+        };
+        let invalid = |src: &str, indent| matches!(&scan(src, indent).1[..], [PhoenixError::Compile { id: CompErrID::InvalidIndentation, .. }]);
+
+        assert!(invalid("a\n   b", Indent::Spaces(4)));
+        assert!(invalid("a\n\tb", Indent::Spaces(4)));
+        assert!(invalid("a\n\t  b", Indent::Tabs));
+        assert!(invalid("a\n  b", Indent::Tabs));
+        assert!(invalid("a\n        b\n    c", Indent::Spaces(4)));
+
+        // Blank and comment only lines do not close the block
+        use TokenType::{Identifier, IndentUp, IndentDown, Newline, Eof};
+        let expected = [Identifier, Newline, IndentUp, Identifier, Newline, Identifier, Newline, IndentDown, Identifier, Eof];
+        for (src, indent) in [("a\n  b\n\n// note\n  c\nd", Indent::Spaces(2)), ("a\n\tb\n   \n\t// note\n\tc\nd", Indent::Tabs),
+            ("a\n  b\n/* note */ // more\n      /* one */ /* two\n */\n  c\nd", Indent::Spaces(2))] {
+            let (tokens, errors) = scan(src, indent);
+            assert!(errors.is_empty());
+            assert_eq!(tokens, expected);
+        }
+        let vm = run_src("let a = 1\n    /* note */\nlet b = 2").unwrap();
+        assert!(matches!(vm.stack[1], Value::Int(2)));
+    }
+
+    #[test]
+    pub fn block_values() {
+        // The value of a block is its last statement, its locals are dropped from under it
+        let vm = run_src("let c = { let d = 1; let e = 2; e }\nlet f = { let g = c; let h = { let i = g + 1; i * 2 }; h + g }\nlet j = { 7 }").unwrap();
+        assert!(matches!(vm.stack[0], Value::Int(2)));
+        assert!(matches!(vm.stack[1], Value::Int(8)));
+        assert!(matches!(vm.stack[2], Value::Int(7)));
+
+        // A `;` after the last statement drops its value
+        let void = |src| matches!(compile_src(src).err().as_deref(), Some([PhoenixError::Compile { id: CompErrID::TypeError, msg, .. }]) if msg.contains("non-void"));
+        assert!(void("print { 1; }"));
+        assert!(void("print { let a = 1; a; }"));
+        assert!(!void("print { 1 }"));
+    }
+
+    #[test]
+    pub fn block_comments() {
+        let vm = run_src("let a = 1 /* outer /* inner */ still a * comment / */ + 2\nlet b = a /**/ * 3").unwrap();
+        assert!(matches!(vm.stack[0], Value::Int(3)));
+        assert!(matches!(vm.stack[1], Value::Int(9)));
+
+        let errs = compile_src("let a = 1 /* open /* closed */\nlet b = 2").err().unwrap();
+        assert!(matches!(&errs[..], [PhoenixError::Compile { id: CompErrID::UnterminatedComment, span: Span { start: 10, end: 12, .. }, .. }]));
+    }
+
+    #[test]
+    pub fn lexical_recovery() {
+        let src = "let a = 1 \u{2603} 2\nlet b = 0b102\nlet c = \"x\\q y\"\nlet d = 1\nlet e = 'a' + d";
+        let (tokens, _, errors) = Scanner::new(src.to_owned(), FileId(0)).scan();
+        assert!(matches!(&errors[..], [PhoenixError::Compile { id: CompErrID::InvalidCharacter, .. }, PhoenixError::Compile { id: CompErrID::InvalidNumber, .. },
+            PhoenixError::Compile { id: CompErrID::InvalidEscape, .. }]));
+        assert_eq!(tokens.iter().filter(|token| token.ty == TokenType::Error).count(), 2);
+
+        // Statements the scanner reported are skipped, the parser still reports the rest of the file
+        let errs = compile_src(src).err().unwrap();
+        assert_eq!(errs.len(), 4);
+        assert!(matches!(&errs[3], PhoenixError::Compile { id: CompErrID::TypeError, .. }));
+    }
+
+    #[test]
+    pub fn logical_lines() {
+        let vm = run_src("let a = (1\n+ 2\n    )\nlet b = a *\n        3 +\n  1\nlet c = \"x\ny\" + \"z\"\n\n// comment\nlet d = c").unwrap();
+        assert!(matches!(vm.stack[0], Value::Int(3)));
+        assert!(matches!(vm.stack[1], Value::Int(10)));
+        assert!(matches!(&vm.stack[2], Value::Str(str) if &**str == "x\nyz"));
+
+        use TokenType::{Identifier, Newline, Eof};
+        let (tokens, _, errors) = Scanner::new("a\n\n\nb =\n   c\n[a,\n b]\n".to_owned(), FileId(0)).scan();
+        assert!(errors.is_empty());
+        let newlines = tokens.iter().filter(|token| token.ty == Newline).count();
+        assert_eq!(newlines, 3);
+        assert!(matches!(tokens[..], [_, Token { ty: Newline, .. }, Token { ty: Identifier, .. }, ..]));
+        assert_eq!(tokens.last().unwrap().ty, Eof);
+    }
+
+    #[test]
+    pub fn broken_inputs() {
+        let mut corpus: Vec<std::string::String> = [
+            "", "(", ")", "(1", "(1 +", "1 +", "let", "let a", "let a =", "let a: = 1", "let 1 = 2", "let a: Int 1", "1 = 2", "a b",
+            "+", "-", "- -", "1 as", "1 as 2", "[1]", "1 [", "{", "}", "{ 1", "{ 1 }\n}", "alias", "pub", "pub alias A =", "alias A = ",
+            "\"{}\"", "\"{ 1 +", "\"a { b", "'", "''", "'ab'", "0x", "1e", "1.5u8", "0b2", "print", "print let a = 1",
+            "let a = 1\n        print a\n    print a", "\tlet a = 1", "a +=", "1 .", "1 , 2", "1 ; ;", "/* /* */", "\0", "\u{2603}",
+            "let a = {", "let a = (\n1\n", "a\n    b\n  c", "let a = 1 as Int as", "x +| y +% z",
+        ].into_iter().map(|src| src.to_owned()).collect();
+        corpus.push("(".repeat(10_000));
+        corpus.push("{ ".repeat(10_000));
+        corpus.push(format!("\"{}\"", "{1}".repeat(300)));
+        corpus.push((0..300).map(|i| format!("{{ x{i} }}\n")).collect());
+
+        // Fragments of valid programs put together at random
+        let fragments = ["let ", "a", " = ", "1", "2.5", "\"s { a } t\"", "'c'", " + ", " - ", " * ", " / ", " +% ", " as ", "Int", "U8", "(", ")", "{ ", " }",
+            "\n", "\n    ", ";", ":", "print ", "alias ", "pub ", "-", "0xFF", "1e3", "true", "=", "[", "]", ".", ","];
+        let mut seed = 0x2545F4914F6CDD1Du64;
+        for _ in 0..2_000 {
+            let len = (seed % 12) as usize + 1;
+            corpus.push((0..len).map(|_| { seed ^= seed << 13; seed ^= seed >> 7; seed ^= seed << 17; fragments[(seed % fragments.len() as u64) as usize] }).collect());
+        }
+
+        for src in corpus {
+            let result = std::panic::catch_unwind(|| { let _ = compile_src(&src); });
+            assert!(result.is_ok(), "compiling {src:?} panicked");
+        }
+    }
+
     //#[test]
     pub fn test_general() {
         let a = Rc::new(RefCell::new(1));
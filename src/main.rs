@@ -4,15 +4,17 @@ use std::path::PathBuf;
 use std::fs;
 
 use clap::{Parser, CommandFactory, error::ErrorKind};
-use cli::PhoenixCli;
+use cli::{PhoenixCli, Emit};
+use phoenixlang::{Compiler, Profile, error::PhoenixError, source::SourceMap};
 mod cli;
 
 fn main() {
     let cli = PhoenixCli::parse();
 
     match cli.subcmd {
-        cli::Commands::Compile { scan, project } => compile(scan, project),
-        cli::Commands::Run { scan, compiled, debug, file } => todo!(),
+        cli::Commands::Compile { scan, emit: Some(Emit::Ast), project } => emit_ast(project),
+        cli::Commands::Compile { scan, emit: None, project } => compile(scan, project),
+        cli::Commands::Run { scan, compiled, debug, stack_size, max_stack_size, file } => run(debug, stack_size, max_stack_size, file),
     }
 }
 
@@ -27,3 +29,31 @@ fn compile(scan: bool, file: PathBuf) {
 
     let src =  fs::read_to_string(file).map_err(|err| panic!("{}", err.to_string())).unwrap();
 }
+
+fn emit_ast(project: PathBuf) {
+    let mut sources = SourceMap::default();
+    match Compiler::emit_ast(project, &mut sources) {
+        Ok(ast) => print!("{ast}"),
+        Err(errors) => {
+            errors.iter().for_each(|err| eprintln!("{}", err.render(&sources)));
+            std::process::exit(1)
+        }
+    }
+}
+
+/// Runs a project directory, only debug runs are left unoptimized
+fn run(debug: bool, stack_size: Option<usize>, max_stack_size: Option<usize>, project: PathBuf) {
+    let mut sources = SourceMap::default();
+    let fail = |errors: Vec<PhoenixError>, sources: &SourceMap| -> ! {
+        errors.iter().for_each(|err| eprintln!("{}", err.render(sources)));
+        std::process::exit(1)
+    };
+
+    let stack = Compiler::stack_config(&project).unwrap_or_else(|errors| fail(errors, &sources))
+        .with_sizes(stack_size, max_stack_size).unwrap_or_else(|msg| PhoenixCli::command().error(ErrorKind::InvalidValue, msg).exit());
+    let chunk = Compiler::compile(project, if debug { Profile::Debug } else { Profile::Release }, &mut sources).unwrap_or_else(|errors| fail(errors, &sources));
+    match phoenixlang::run(chunk, stack, debug) {
+        Ok(code) => std::process::exit(code as i32),
+        Err(err) => fail(vec![err], &sources),
+    }
+}
@@ -0,0 +1,81 @@
+use std::{fmt::{Debug, Display}, sync::Arc};
+
+/// Index of a file registered in a [`SourceMap`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FileId(pub u32);
+
+/// Byte range `start..end` of a file, rows and columns are only computed when a diagnostic is shown
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span { pub file: FileId, pub start: u32, pub end: u32 }
+
+impl Span {
+    pub fn new(file: FileId, start: u32, end: u32) -> Self { Self { file, start, end } }
+
+    /// Span from the start of `self` to the end of `other`, both must be in the same file
+    pub fn to(self, other: Span) -> Self { Self { file: self.file, start: self.start.min(other.start), end: self.end.max(other.end) } }
+}
+
+impl Debug for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { write!(f, "#{}[{}..{}]", self.file.0, self.start, self.end) }
+}
+
+pub struct SourceFile {
+    name: Arc<str>,
+    src: Arc<str>,
+    /// Byte offset at which every line begins, the first one is always 0
+    line_starts: Vec<u32>,
+}
+
+impl SourceFile {
+    pub fn new(name: &str, src: &str) -> Self {
+        let line_starts = std::iter::once(0).chain(src.match_indices('\n').map(|(i, _)| i as u32 + 1)).collect();
+        Self { name: name.into(), src: src.into(), line_starts }
+    }
+
+    pub fn name(&self) -> &str { &self.name }
+    pub fn src(&self) -> &str { &self.src }
+    pub fn slice(&self, span: Span) -> &str { &self.src[span.start as usize..span.end as usize] }
+
+    /// Zero based line containing the byte at `offset`
+    pub fn line(&self, offset: u32) -> usize { self.line_starts.partition_point(|start| *start <= offset) - 1 }
+
+    /// Text of a zero based line, without its line break
+    pub fn line_text(&self, line: usize) -> &str {
+        let start = self.line_starts[line] as usize;
+        let end = self.line_starts.get(line + 1).map_or(self.src.len(), |end| *end as usize);
+        self.src[start..end].trim_end_matches(['\n', '\r'])
+    }
+
+    /// One based row and column of the byte at `offset`, columns count characters
+    pub fn location(&self, offset: u32) -> (usize, usize) {
+        let line = self.line(offset);
+        let start = self.line_starts[line] as usize;
+        let offset = (offset as usize).min(self.src.len());
+        (line + 1, self.src[start..offset].chars().count() + 1)
+    }
+}
+
+#[derive(Default)]
+pub struct SourceMap { files: Vec<Arc<SourceFile>> }
+
+impl SourceMap {
+    pub fn add(&mut self, name: &str, src: &str) -> FileId {
+        self.files.push(Arc::new(SourceFile::new(name, src)));
+        FileId(self.files.len() as u32 - 1)
+    }
+
+    pub fn get(&self, file: FileId) -> &Arc<SourceFile> { &self.files[file.0 as usize] }
+
+    pub fn location(&self, span: Span) -> Location {
+        let file = self.get(span.file);
+        let (row, col) = file.location(span.start);
+        Location { file: file.name.clone(), row, col }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location { pub file: Arc<str>, pub row: usize, pub col: usize }
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { write!(f, "{}:{}:{}", self.file, self.row, self.col) }
+}
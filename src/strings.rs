@@ -15,15 +15,38 @@ impl InternStrSync {
     }
 }
 
-pub struct InternStr(HashSet<Rc<str>, BuildHasherDefault<AHasher>>);
+/// Strings of the VM, the bytes they hold are counted so they also trigger garbage collections
+pub struct InternStr {
+    set: HashSet<Rc<str>, BuildHasherDefault<AHasher>>,
+    bytes: usize,
+    /// Bytes interned since the last sweep
+    allocated: usize,
+}
 
 impl InternStr {
-    pub fn new() -> Self { InternStr(Default::default()) }
+    pub fn new() -> Self { InternStr { set: Default::default(), bytes: 0, allocated: 0 } }
 
     pub fn intern_str(&mut self, str: &str) -> Rc<str> { 
-        match self.0.get(str) {
+        match self.set.get(str) {
             Some(str) => Rc::clone(str),
-            None => { let rc = Rc::<str>::from(str); self.0.insert(rc.clone()); rc }
+            None => {
+                let rc = Rc::<str>::from(str); self.set.insert(rc.clone());
+                self.bytes += str.len(); self.allocated += str.len();
+                rc
+            }
         }
     }
+
+    pub fn bytes(&self) -> usize { self.bytes }
+
+    pub fn allocated(&self) -> usize { self.allocated }
+
+    /// Drops the strings only this table references, returns how many
+    pub fn sweep(&mut self) -> usize {
+        let before = self.set.len();
+        let mut bytes = 0;
+        self.set.retain(|str| { let used = Rc::strong_count(str) > 1; if used { bytes += str.len() } used });
+        (self.bytes, self.allocated) = (bytes, 0);
+        before - self.set.len()
+    }
 }
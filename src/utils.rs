@@ -10,15 +10,15 @@ macro_rules! op_codes {
         }
 
         impl $name {
-            pub fn size() -> Vec<u8> { vec![$($size,)*] }
+            /// Size of every instruction in bytes, opcode included, indexed by opcode
+            pub fn size() -> &'static [u8] { &[$($size,)*] }
         }
 
+        // Opcodes are numbered in order from 0, so they are their own index in the table
         impl std::convert::From<u8> for $name {
             fn from(v: u8) -> Self {
-                match v {
-                    $(x if x == $name::$vname as u8 => $name::$vname,)*
-                    _ => unreachable!(),
-                }
+                const ALL: &[$name] = &[$($name::$vname,)*];
+                ALL[v as usize]
             }
         }
     }
@@ -33,6 +33,9 @@ impl OwnedChars {
     pub fn new(s: String) -> Self {
         Self { s, index: 0 }
     }
+
+    /// Whole string, including the characters already iterated over
+    pub fn as_str(&self) -> &str { &self.s }
 }
 
 impl Iterator for OwnedChars {
@@ -0,0 +1,110 @@
+use std::{fmt::Display, mem, time::Duration};
+
+use super::value::Value;
+
+/// Handle to an object owned by the [`Heap`] that allocated it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Gc(u32);
+
+/// Values the VM keeps outside the stack, they can reference each other through `Value::Upv`
+#[derive(Debug)]
+pub enum Object {
+    /// Variable shared with the code capturing it
+    Upv(Value),
+}
+
+impl Object {
+    /// Handles held by the object
+    fn trace(&self, gray: &mut Vec<Gc>) {
+        match self { Object::Upv(value) => if let Value::Upv(gc) = value { gray.push(*gc) } }
+    }
+}
+
+/// Bytes allocated since the last collection that trigger the next one, at the least
+const MIN_THRESHOLD: usize = 1 << 20;
+
+/// Mark and sweep heap, objects live until a collection finds them unreachable from the roots given to it,
+/// cycles included
+#[derive(Debug)]
+pub struct Heap {
+    objects: Vec<Option<Object>>,
+    marks: Vec<bool>,
+    free: Vec<u32>,
+    /// Objects currently allocated
+    live: usize,
+    /// Bytes allocated since the last collection
+    allocated: usize,
+    threshold: usize,
+    pub stats: GcStats,
+}
+
+/// What the garbage collector did so far
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GcStats {
+    /// Bytes held by heap objects and interned strings
+    pub heap_size: usize,
+    pub collections: usize,
+    pub freed_objects: usize,
+    pub freed_strings: usize,
+    pub last_pause: Duration,
+    pub max_pause: Duration,
+    pub total_pause: Duration,
+}
+
+impl Display for GcStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} bytes on the heap, {} collections freed {} objects and {} strings, paused {:?} in total and {:?} at most",
+            self.heap_size, self.collections, self.freed_objects, self.freed_strings, self.total_pause, self.max_pause)
+    }
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Self { objects: vec![], marks: vec![], free: vec![], live: 0, allocated: 0, threshold: MIN_THRESHOLD, stats: GcStats::default() }
+    }
+
+    /// Never collects, collections only happen when the VM knows all its roots
+    pub fn alloc(&mut self, object: Object) -> Gc {
+        self.live += 1;
+        self.allocated += mem::size_of::<Object>();
+        match self.free.pop() {
+            Some(at) => { self.objects[at as usize] = Some(object); Gc(at) }
+            None => { self.objects.push(Some(object)); self.marks.push(false); Gc(self.objects.len() as u32 - 1) }
+        }
+    }
+
+    pub fn get(&self, gc: Gc) -> &Value {
+        match &self.objects[gc.0 as usize] { Some(Object::Upv(value)) => value, None => unreachable!() }
+    }
+
+    pub fn get_mut(&mut self, gc: Gc) -> &mut Value {
+        match &mut self.objects[gc.0 as usize] { Some(Object::Upv(value)) => value, None => unreachable!() }
+    }
+
+    /// Bytes held by the allocated objects
+    pub fn size(&self) -> usize { self.live * mem::size_of::<Object>() }
+
+    /// Whether enough was allocated since the last collection, `extra` counts other allocations since then like strings
+    pub fn should_collect(&self, extra: usize) -> bool { self.allocated + extra > self.threshold }
+
+    /// The next collection waits until as much as survived the last one is allocated again, `extra` counts other surviving bytes
+    pub fn schedule(&mut self, extra: usize) { self.threshold = (self.size() + extra).max(MIN_THRESHOLD) }
+
+    /// Frees every object not reachable from `roots`
+    pub fn collect<'a>(&mut self, roots: impl Iterator<Item = &'a Value>) -> usize {
+        let mut gray: Vec<Gc> = roots.filter_map(|value| match value { Value::Upv(gc) => Some(*gc), _ => None }).collect();
+        while let Some(gc) = gray.pop() {
+            let at = gc.0 as usize;
+            if mem::replace(&mut self.marks[at], true) { continue }
+            if let Some(object) = &self.objects[at] { object.trace(&mut gray) }
+        }
+
+        let mut freed = 0;
+        for (at, (object, mark)) in self.objects.iter_mut().zip(self.marks.iter_mut()).enumerate() {
+            if !mem::take(mark) && object.take().is_some() { self.free.push(at as u32); freed += 1 }
+        }
+        self.live -= freed;
+        self.allocated = 0;
+        freed
+    }
+}
@@ -1,15 +1,36 @@
-use std::{cell::RefCell, rc::Rc, usize, collections::{HashMap, HashSet}, hash::BuildHasherDefault, ops::DerefMut, ops::Deref, mem::{self, MaybeUninit}};
+use std::{cell::RefCell, rc::Rc, usize, collections::{HashMap, HashSet}, hash::BuildHasherDefault, ops::DerefMut, ops::Deref, mem, time::Instant};
 use ahash::AHasher;
 
-use crate::{compiler::chunk::{Chunk, Const}, flamebytecode::{FBOpCode, debug, run}, strings::{InternStrSync, InternStr}, STACK_LENGTH};
-use self::value::Value;
+use crate::{error::PhoenixError, compiler::chunk::{Chunk, Const}, flamebytecode::{FBOpCode, debug, run}, strings::{InternStrSync, InternStr}};
+use self::{value::Value, heap::{Heap, GcStats}};
 
 pub mod value;
+pub mod heap;
+
+/// Slots the stack starts with and the most it grows to when full, it never grows when both are equal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StackConfig { pub size: usize, pub max: usize }
+
+impl Default for StackConfig { fn default() -> Self { Self { size: 512, max: 1 << 16 } }}
+
+impl StackConfig {
+    /// Overrides the given sizes, a maximum that is not given is raised to the size if needed
+    pub fn with_sizes(self, size: Option<usize>, max: Option<usize>) -> Result<Self, String> {
+        let size = size.unwrap_or(self.size);
+        let max = max.unwrap_or(self.max.max(size));
+        if size == 0 { return Err(String::from("The stack size must be at least 1")) }
+        if max < size { return Err(format!("The maximum stack size {max} is smaller than the stack size {size}")) }
+        Ok(Self { size, max })
+    }
+}
 
 #[derive(Debug)]
 pub struct Stack { 
-    array: [Value; STACK_LENGTH],
-    top: usize
+    array: Vec<Value>,
+    top: usize,
+    max: usize,
+    /// Call frames on the stack, the top level code is the only one until calls exist
+    pub frames: usize,
 }
 
 
@@ -17,15 +38,26 @@ impl Deref for Stack { type Target = [Value]; fn deref(&self) -> &Self::Target {
 impl DerefMut for Stack { fn deref_mut(&mut self) -> &mut Self::Target { &mut self.array }}
 
 impl Stack {
-    pub fn new() -> Self { Self { array: {
-    let mut data: [MaybeUninit<Value>; STACK_LENGTH] = unsafe { MaybeUninit::uninit().assume_init() };
+    pub fn new(config: StackConfig) -> Self { Self { array: vec![Value::default(); config.size], top: 0, max: config.max, frames: 1 } }
+
+    pub fn push(&mut self, value: Value) -> Result<(), PhoenixError> {
+        if self.top == self.array.len() { self.grow()? }
+        self.array[self.top] = value; self.top += 1;
+        Ok(())
+    }
 
-    for elem in &mut data[..] { elem.write(Value::default()); }
+    pub fn pop(&mut self) -> Result<Value, PhoenixError> {
+        self.top = self.top.checked_sub(1).ok_or_else(|| PhoenixError::Runtime(String::from("Stack underflow, a value was popped from the empty stack")))?;
+        Ok(mem::take(&mut self.array[self.top]))
+    }
 
-    unsafe { mem::transmute::<_, [Value; STACK_LENGTH]>(data) } },
-    top: 0 } }
-    pub fn push(&mut self, value: Value) { self.array[self.top] = value; self.top += 1; }
-    pub fn pop(&mut self) -> Value { self.top -= 1; mem::take(&mut self.array[self.top]) }
+    /// Doubles the slots up to the maximum, values keep their address
+    #[cold]
+    fn grow(&mut self) -> Result<(), PhoenixError> {
+        if self.array.len() >= self.max { return Err(PhoenixError::StackOverflow { depth: self.frames, size: self.max }) }
+        self.array.resize((self.array.len() * 2).clamp(1, self.max), Value::default());
+        Ok(())
+    }
 }
 
 pub struct Vm {
@@ -33,22 +65,46 @@ pub struct Vm {
     pub pc: u64,
     pub stack: Stack,
     pub strings: InternStr,
-    pub globals: HashMap<Rc<str>, Value, BuildHasherDefault<AHasher>>
+    pub globals: HashMap<Rc<str>, Value, BuildHasherDefault<AHasher>>,
+    pub heap: Heap,
 }
 
 impl Vm {
-    pub fn run(mut self, debug_flag: bool) -> u8 {
-        loop {
-            let byte = self.chunk.code[self.pc as usize];
-            let size = FBOpCode::size()[byte as usize] as usize;
-            let exit_code = run(&mut self, size);
-            //println!("|{:?}\n", &self.stack[0..self.stack.top]);
-            if let Some(code) = exit_code { return code }
-            if debug_flag { debug(self.pc, &self.chunk.code[self.pc as usize..self.pc as usize + size]); }
-            if self.chunk.code.len() - size <= self.pc as usize { break; } 
+    pub fn new(chunk: Chunk) -> Self { Self { chunk, pc: 0, stack: Stack::new(StackConfig::default()), strings: InternStr::new(), globals: Default::default(), heap: Heap::new() } }
+
+    pub fn with_stack(mut self, config: StackConfig) -> Self { self.stack = Stack::new(config); self }
+
+    pub fn run(&mut self, debug_flag: bool) -> Result<u8, PhoenixError> {
+        let sizes = FBOpCode::size();
+        // Optimized code may be empty
+        while let Some(&byte) = self.chunk.code.get(self.pc as usize) {
+            if let Some(code) = run(self, FBOpCode::from(byte))? { return Ok(code) }
+            let size = sizes[byte as usize] as usize;
+            if debug_flag { debug(self.pc, &self.chunk.code[self.pc as usize..self.pc as usize + size]) }
             self.pc += size as u64;
+            // Every value is on the stack or in a global between two instructions
+            if self.heap.should_collect(self.strings.allocated()) { self.collect_garbage() }
         }
-        println!("\n\n{:?}\n", &self.stack[0..self.stack.top]);
-        0
+        if debug_flag { println!("\n\n{:?}\n\ngc: {}\n", &self.stack[0..self.stack.top], self.gc_stats()) }
+        Ok(0)
     }
+
+    /// Frees the heap objects unreachable from the stack and the globals, then the strings no value holds anymore
+    pub fn collect_garbage(&mut self) {
+        let start = Instant::now();
+        let freed = self.heap.collect(self.stack[0..self.stack.top].iter().chain(self.globals.values()));
+        let freed_strings = self.strings.sweep();
+        self.heap.schedule(self.strings.bytes());
+
+        let stats = &mut self.heap.stats;
+        let pause = start.elapsed();
+        stats.collections += 1;
+        stats.freed_objects += freed;
+        stats.freed_strings += freed_strings;
+        stats.last_pause = pause;
+        stats.max_pause = stats.max_pause.max(pause);
+        stats.total_pause += pause;
+    }
+
+    pub fn gc_stats(&self) -> GcStats { GcStats { heap_size: self.heap.size() + self.strings.bytes(), ..self.heap.stats.clone() } }
 }
@@ -2,8 +2,11 @@ use std::{str::Bytes, vec, u8, cell::{RefCell, Cell, Ref}, mem, rc::Rc, fmt::{Di
 
 
 use crate::compiler::chunk::{Chunk, Const};
+use crate::error::PhoenixError;
+use crate::decimal::Decimal;
+use crate::flamebytecode::CastTy;
 
-use super::Vm;
+use super::{Vm, heap::{Gc, Heap, Object}};
 
 #[derive(Clone, Debug)]
 pub enum Pointer { Local(usize), Global(Rc<str>) }
@@ -13,24 +16,34 @@ impl Display for Pointer {
     }
 }
 
+/// Everything the VM holds takes 24 bytes, numbers, Bools and Chars are stored inline and Strs and upvalues behind
+/// one reference counted pointer. The variant needs no byte of its own, it is kept in the scale values a [`Decimal`] never uses.
 #[derive(Clone, Debug)]
 pub enum Value {
     Bool(bool),
     Int(i64),
-    Dec(f64),
+    Dec(Decimal),
     Str(Rc<str>),
     Char(char),
+    I8(i8), I16(i16), I32(i32), I64(i64),
+    U8(u8), U16(u16), U32(u32), U64(u64),
+    F32(f32), F64(f64),
     Ptr(Pointer),
-    Upv(Rc<RefCell<Value>>)
+    /// Variable shared through the heap
+    Upv(Gc)
 }
 
 impl Default for Value { fn default() -> Self { Self::Bool(true) }}
 
+/// Upvalues are only shown as such, use [`Value::display`] to show the value they share
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Value::Bool(b) => write!(f, "{b}"), Value::Int(i) => write!(f, "{i}"), Value::Dec(d) => write!(f, "{d:?}"), Value::Str(str) => write!(f, "{}", *str), 
-            Value::Char(c) => write!(f, "{c}"), Value::Ptr(ptr) => write!(f, "{ptr}"), Value::Upv(upv) => write!(f, "{}", upv.borrow()) }}
+            Value::Bool(b) => write!(f, "{b}"), Value::Int(i) => write!(f, "{i}"), Value::Dec(d) => write!(f, "{d}"), Value::Str(str) => write!(f, "{}", *str), 
+            Value::Char(c) => write!(f, "{c}"),
+            Value::I8(i) => write!(f, "{i}"), Value::I16(i) => write!(f, "{i}"), Value::I32(i) => write!(f, "{i}"), Value::I64(i) => write!(f, "{i}"),
+            Value::U8(i) => write!(f, "{i}"), Value::U16(i) => write!(f, "{i}"), Value::U32(i) => write!(f, "{i}"), Value::U64(i) => write!(f, "{i}"),
+            Value::F32(d) => write!(f, "{d:?}"), Value::F64(d) => write!(f, "{d:?}"), Value::Ptr(ptr) => write!(f, "{ptr}"), Value::Upv(_) => write!(f, "[upvalue]") }}
 }
 
 impl Vm {
@@ -43,13 +56,72 @@ impl Vm {
 }
 
 impl Vm {
+    pub fn cast(&mut self, value: Value, to: CastTy) -> Result<Value, PhoenixError> {
+        macro_rules! cast_err { ($($arg:tt)*) => { Err(PhoenixError::Runtime(format!($($arg)*))) }; }
+
+        // Every number goes through the widest representation of its kind
+        enum Num { Int(i128), Dec(Decimal), Float(f64) }
+        let num = match &value {
+            Value::Int(v) => Num::Int(*v as i128), Value::Char(c) => Num::Int(*c as i128), Value::Bool(b) => Num::Int(*b as i128),
+            Value::I8(v) => Num::Int(*v as i128), Value::I16(v) => Num::Int(*v as i128), Value::I32(v) => Num::Int(*v as i128), Value::I64(v) => Num::Int(*v as i128),
+            Value::U8(v) => Num::Int(*v as i128), Value::U16(v) => Num::Int(*v as i128), Value::U32(v) => Num::Int(*v as i128), Value::U64(v) => Num::Int(*v as i128),
+            Value::Dec(v) => Num::Dec(*v), Value::F32(v) => Num::Float(*v as f64), Value::F64(v) => Num::Float(*v),
+            Value::Str(str) if to == CastTy::Str => return Ok(value),
+            Value::Str(str) => match to {
+                CastTy::Char => {
+                    let mut chars = str.chars();
+                    return match (chars.next(), chars.next()) {
+                        (Some(c), None) => Ok(Value::Char(c)),
+                        _ => cast_err!("Cannot cast Str \"{str}\" to Char, it must contain exactly one character"),
+                    }
+                }
+                CastTy::Dec => Num::Dec(str.parse().or_else(|_| cast_err!("Cannot cast Str \"{str}\" to {to:?}"))?),
+                CastTy::F32 | CastTy::F64 => Num::Float(str.parse().or_else(|_| cast_err!("Cannot cast Str \"{str}\" to {to:?}"))?),
+                _ => Num::Int(str.parse().or_else(|_| cast_err!("Cannot cast Str \"{str}\" to {to:?}"))?),
+            }
+            _ => unreachable!(),
+        };
+
+        let int = match (num, to) {
+            (_, CastTy::Str) => { let str = value.display(&self.heap).to_string(); return Ok(Value::Str(self.strings.intern_str(&str))) }
+            (Num::Int(i), CastTy::Dec) => return Ok(Value::Dec(Decimal::from_int(i))),
+            (Num::Dec(d), CastTy::Dec) => return Ok(Value::Dec(d)),
+            (Num::Float(f), CastTy::Dec) => match Decimal::from_f64(f) {
+                Some(d) => return Ok(Value::Dec(d)),
+                None => return cast_err!("{value} cannot be represented as Dec"),
+            }
+            (Num::Int(i), CastTy::F32) => return Ok(Value::F32(i as f32)),
+            (Num::Int(i), CastTy::F64) => return Ok(Value::F64(i as f64)),
+            (Num::Dec(d), CastTy::F32) => return Ok(Value::F32(d.to_f64() as f32)),
+            (Num::Dec(d), CastTy::F64) => return Ok(Value::F64(d.to_f64())),
+            (Num::Float(f), CastTy::F32) => return Ok(Value::F32(f as f32)),
+            (Num::Float(f), CastTy::F64) => return Ok(Value::F64(f)),
+            (Num::Int(i), _) => i,
+            (Num::Dec(d), _) => d.trunc(),
+            // Saturates to i128 bounds, which no target fits, so the range check below catches it
+            (Num::Float(d), _) if !d.is_nan() => d.trunc() as i128,
+            (Num::Float(d), _) => return cast_err!("{value} cannot be represented as {to:?}"),
+        };
+
+        let res = match to {
+            CastTy::Int => int.try_into().ok().map(Value::Int),
+            CastTy::Char => u32::try_from(int).ok().and_then(char::from_u32).map(Value::Char),
+            CastTy::I8 => int.try_into().ok().map(Value::I8), CastTy::I16 => int.try_into().ok().map(Value::I16),
+            CastTy::I32 => int.try_into().ok().map(Value::I32), CastTy::I64 => int.try_into().ok().map(Value::I64),
+            CastTy::U8 => int.try_into().ok().map(Value::U8), CastTy::U16 => int.try_into().ok().map(Value::U16),
+            CastTy::U32 => int.try_into().ok().map(Value::U32), CastTy::U64 => int.try_into().ok().map(Value::U64),
+            CastTy::Dec | CastTy::F32 | CastTy::F64 | CastTy::Str => unreachable!(),
+        };
+        match res { Some(res) => Ok(res), None => cast_err!("{value} cannot be represented as {to:?}") }
+    }
+
     pub fn with_depnt_upved<R>(&mut self, addr: usize, f: impl FnOnce(Box<dyn DerefMut<Target = Value> + '_>) -> R) -> R {
         f( match match &self.stack[addr] {
             Value::Ptr(Pointer::Local(addr)) => { let addr = *addr; &mut self.stack[addr] }
             Value::Ptr(Pointer::Global(symname)) => self.globals.get_mut(symname).unwrap(),
             _ => &mut self.stack[addr]
         } {
-            Value::Upv(rc) => Box::new(rc.borrow_mut()),
+            Value::Upv(gc) => { let gc = *gc; Box::new(self.heap.get_mut(gc)) }
             mut_ref => Box::new(mut_ref)
         })
     }
@@ -57,12 +129,11 @@ impl Vm {
 
 impl Value {
     pub fn promote_upv(&mut self, vm: &mut Vm) {
-        self.with_depointed(vm, |val| {
-            match val {
-                Value::Upv(_) => {}
-                _ => {let value = mem::take(val); *val = Value::Upv(Rc::new(RefCell::new(value))); } 
-            }
-        })
+        let value = self.with_depointed(vm, |val| match val { Value::Upv(_) => None, _ => Some(mem::take(val)) });
+        if let Some(value) = value {
+            let gc = vm.heap.alloc(Object::Upv(value));
+            self.with_depointed(vm, |val| *val = Value::Upv(gc));
+        }
     }
     pub fn with_depointed<R>(&mut self, vm: &mut Vm, f: impl FnOnce(&mut Value) -> R) -> R {
         f(match self {
@@ -80,9 +151,19 @@ impl Value {
             v => return Cow::Owned(v)
         }
     }
-    pub fn deupvalue(&self, vm: &Vm) -> Box<dyn Deref<Target = Value> + '_> {
+    /// The value as printed, upvalues show the value they share
+    pub fn display<'a>(&'a self, heap: &'a Heap) -> impl Display + 'a {
+        struct Shown<'a>(&'a Value, &'a Heap);
+        impl Display for Shown<'_> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self.0 { Value::Upv(gc) => Shown(self.1.get(*gc), self.1).fmt(f), value => value.fmt(f) }
+            }
+        }
+        Shown(self, heap)
+    }
+    pub fn deupvalue<'a>(&'a self, vm: &'a Vm) -> Box<dyn Deref<Target = Value> + 'a> {
         match self {
-            Value::Upv(rc) => Box::new(rc.borrow()),
+            Value::Upv(gc) => Box::new(vm.heap.get(*gc)),
             val => Box::new(val)
         }
     }